@@ -1,14 +1,28 @@
 use std::str::FromStr;
+use std::time::Duration;
 
 use ldk_node::{
     bitcoin::secp256k1::PublicKey, lightning::ln::msgs::SocketAddress,
     lightning_invoice::Bolt11Invoice,
 };
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
+use crate::error::ClientError;
 use crate::{
+    ChainTip, ChannelConfigUpdate, ChannelStatus, ClaimHoldInvoiceRequest, CloseChannelRequest,
+    CloseChannelResponse, CompactChannel, ConnectPeerRequest, CustomTlv, ErrorResponse,
     FaucetRequest, FundingAddress, GetBalanceResponse, GetInvoiceRequest, GetInvoiceResponse,
-    GetPaymentResponse, ListChannelsResponse, LspConfig, OpenChannelRequest, OpenChannelResponse,
-    PayInvoiceRequest, PayInvoiceResponse,
+    GetOfferRequest, GetOfferResponse, GetPaymentResponse, HoldInvoiceRequest,
+    InitiateRefundRequest, InitiateRefundResponse, JitInvoiceRequest, KeysendRequest,
+    KeysendResponse, ListChannelsQuery, ListChannelsResponse, ListClosedChannelsResponse,
+    ListHeldPaymentsResponse, ListPaymentsQuery, ListPaymentsResponse, ListPeersResponse,
+    ListWebhookDeliveriesResponse, LspConfig, Lsps1Config, Lsps1CreateOrderRequest,
+    Lsps1ListOrdersResponse, Lsps1Order, Lsps5ListWebhooksResponse, Lsps5SetWebhookRequest,
+    Lsps5Webhook, MineBlocksRequest, MineBlocksResponse, NodeEvent, OnchainSendRequest,
+    OnchainSendResponse, OpenChannelRequest, OpenChannelResponse, PayInvoiceRequest,
+    PayInvoiceResponse, PayOfferRequest, PayOfferResponse, Peer, ReorgRequest, ReorgResponse,
+    RequestRefundRequest, RequestRefundResponse,
 };
 
 #[derive(Debug)]
@@ -16,6 +30,76 @@ pub struct LspsClient {
     base_url: String,
 }
 
+/// Turns a response into `T`, or into a [ClientError::Api] if the api returned an error status
+fn parse_response<T: DeserializeOwned>(res: minreq::Response) -> Result<T, ClientError> {
+    if res.status_code >= 400 {
+        let error = res
+            .json::<ErrorResponse>()
+            .unwrap_or_else(|_| ErrorResponse {
+                code: "http_error".to_string(),
+                message: res.reason_phrase.clone(),
+                details: res.as_str().ok().map(|body| body.to_string()),
+            });
+        return Err(ClientError::Api {
+            status: res.status_code,
+            error,
+        });
+    }
+    Ok(res.json::<T>()?)
+}
+
+/// Adds the fields of `query` to the request as url query parameters
+fn with_query<Q: Serialize>(
+    mut req: minreq::Request,
+    query: &Q,
+) -> Result<minreq::Request, ClientError> {
+    let value =
+        serde_json::to_value(query).map_err(|e| ClientError::InvalidRequest(e.to_string()))?;
+    if let serde_json::Value::Object(params) = value {
+        for (key, value) in params {
+            req = match value {
+                serde_json::Value::Null => req,
+                serde_json::Value::String(value) => req.with_param(key, value),
+                value => req.with_param(key, value.to_string()),
+            };
+        }
+    }
+    Ok(req)
+}
+
+/// Blocking iterator over the node events streamed by `GET /events`
+pub struct EventStream {
+    response: minreq::ResponseLazy,
+    line: Vec<u8>,
+}
+
+impl Iterator for EventStream {
+    type Item = Result<NodeEvent, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let byte = match self.response.next()? {
+                Ok((byte, _)) => byte,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if byte != b'\n' {
+                self.line.push(byte);
+                continue;
+            }
+
+            // only `data:` lines carry events, the rest are comments and separators
+            let line = std::mem::take(&mut self.line);
+            let line = String::from_utf8_lossy(&line);
+            if let Some(data) = line.trim_end_matches('\r').strip_prefix("data:") {
+                return Some(
+                    serde_json::from_str(data.trim())
+                        .map_err(|e| ClientError::InvalidResponse(e.to_string())),
+                );
+            }
+        }
+    }
+}
+
 impl LspsClient {
     pub fn new(base_url: &str) -> Self {
         LspsClient {
@@ -23,19 +107,27 @@ impl LspsClient {
         }
     }
 
-    pub fn get_lsps_config(&self) -> Result<LspConfig, minreq::Error> {
+    pub fn get_lsps_config(&self) -> Result<LspConfig, ClientError> {
         let url = format!("{}/config", self.base_url);
-        minreq::get(url).send()?.json::<LspConfig>()
+        parse_response(minreq::get(url).send()?)
     }
 
-    pub fn get_funding_address(&self) -> Result<FundingAddress, minreq::Error> {
+    pub fn get_funding_address(&self) -> Result<FundingAddress, ClientError> {
         let url = format!("{}/funding-address", self.base_url);
-        minreq::get(url).send()?.json::<FundingAddress>()
+        parse_response(minreq::get(url).send()?)
+    }
+
+    pub fn list_channels(&self) -> Result<ListChannelsResponse, ClientError> {
+        self.list_channels_with(&ListChannelsQuery::default())
     }
 
-    pub fn list_channels(&self) -> Result<ListChannelsResponse, minreq::Error> {
+    pub fn list_channels_with(
+        &self,
+        query: &ListChannelsQuery,
+    ) -> Result<ListChannelsResponse, ClientError> {
         let url = format!("{}/channels", self.base_url);
-        minreq::get(url).send()?.json::<ListChannelsResponse>()
+        let req = with_query(minreq::get(url), query)?;
+        parse_response(req.send()?)
     }
 
     pub fn open_channel(
@@ -44,26 +136,120 @@ impl LspsClient {
         ip_port: SocketAddress,
         funding_sats: u64,
         push_sats: u64,
-    ) -> Result<OpenChannelResponse, minreq::Error> {
-        let url = format!("{}/channels", self.base_url);
-        let req = OpenChannelRequest {
+    ) -> Result<OpenChannelResponse, ClientError> {
+        self.open_channel_with(&OpenChannelRequest {
             pubkey,
             ip_port: ip_port.to_string(),
             funding_sats,
             push_sats,
+            wait: true,
+            confirmations: crate::default_channel_confirmations(),
+            channel_config: None,
+            announce: false,
+            zero_conf: false,
+        })
+    }
+
+    /// Opens a channel with all the options of [OpenChannelRequest]
+    pub fn open_channel_with(
+        &self,
+        req: &OpenChannelRequest,
+    ) -> Result<OpenChannelResponse, ClientError> {
+        let url = format!("{}/channels", self.base_url);
+        let res = minreq::post(url).with_json(req)?.send()?;
+        parse_response(res)
+    }
+
+    pub fn get_channel(&self, user_channel_id: u128) -> Result<ChannelStatus, ClientError> {
+        let url = format!("{}/channels/{}", self.base_url, user_channel_id);
+        parse_response(minreq::get(url).send()?)
+    }
+
+    /// Updates the forwarding config of the channel and returns the updated channel
+    pub fn update_channel_config(
+        &self,
+        user_channel_id: u128,
+        config: &ChannelConfigUpdate,
+    ) -> Result<CompactChannel, ClientError> {
+        let url = format!("{}/channels/{}/config", self.base_url, user_channel_id);
+        let res = minreq::put(url).with_json(config)?.send()?;
+        parse_response(res)
+    }
+
+    pub fn connect_peer(
+        &self,
+        pubkey: PublicKey,
+        address: SocketAddress,
+        persist: bool,
+    ) -> Result<Peer, ClientError> {
+        let url = format!("{}/peers", self.base_url);
+        let req = ConnectPeerRequest {
+            pubkey: Some(pubkey),
+            address: address.to_string(),
+            persist,
+        };
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        parse_response(res)
+    }
+
+    pub fn disconnect_peer(&self, pubkey: PublicKey) -> Result<(), ClientError> {
+        let url = format!("{}/peers/{}", self.base_url, pubkey);
+        parse_response::<serde_json::Value>(minreq::delete(url).send()?)?;
+        Ok(())
+    }
+
+    pub fn list_peers(&self) -> Result<ListPeersResponse, ClientError> {
+        let url = format!("{}/peers", self.base_url);
+        parse_response(minreq::get(url).send()?)
+    }
+
+    /// Cooperatively closes the channel, optionally mining until our funds are swept
+    pub fn close_channel(
+        &self,
+        user_channel_id: u128,
+        counterparty_node_id: PublicKey,
+        mine_until_swept: bool,
+    ) -> Result<CloseChannelResponse, ClientError> {
+        let url = format!("{}/channels/{}/close", self.base_url, user_channel_id);
+        let req = CloseChannelRequest {
+            counterparty_node_id,
+            force_close_reason: None,
+            mine_until_swept,
+        };
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        parse_response(res)
+    }
+
+    /// Force closes the channel, optionally mining until our funds are swept
+    pub fn force_close_channel(
+        &self,
+        user_channel_id: u128,
+        counterparty_node_id: PublicKey,
+        reason: Option<String>,
+        mine_until_swept: bool,
+    ) -> Result<CloseChannelResponse, ClientError> {
+        let url = format!("{}/channels/{}/force-close", self.base_url, user_channel_id);
+        let req = CloseChannelRequest {
+            counterparty_node_id,
+            force_close_reason: reason,
+            mine_until_swept,
         };
-        let res = minreq::post(url).with_json(&req).unwrap().send()?;
-        let open_channel_response = res.json::<OpenChannelResponse>()?;
-        Ok(open_channel_response)
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        parse_response(res)
     }
 
-    pub fn pay_invoice(&self, invoice: &Bolt11Invoice) -> Result<String, minreq::Error> {
+    pub fn list_closed_channels(&self) -> Result<ListClosedChannelsResponse, ClientError> {
+        let url = format!("{}/channels/closed", self.base_url);
+        parse_response(minreq::get(url).send()?)
+    }
+
+    pub fn pay_invoice(&self, invoice: &Bolt11Invoice) -> Result<String, ClientError> {
         let url: String = format!("{}/pay-invoice", self.base_url);
         let req = PayInvoiceRequest {
             invoice: invoice.to_string(),
         };
-        let res = minreq::post(url).with_json(&req).unwrap().send()?;
-        Ok(res.json::<PayInvoiceResponse>()?.payment_id)
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        Ok(parse_response::<PayInvoiceResponse>(res)?.payment_id)
     }
 
     pub fn get_invoice(
@@ -71,40 +257,347 @@ impl LspsClient {
         amount_sats: u64,
         description: &str,
         expiry_secs: u32,
-    ) -> Result<Bolt11Invoice, minreq::Error> {
+    ) -> Result<Bolt11Invoice, ClientError> {
         let url = format!("{}/get-invoice", self.base_url);
         let req = GetInvoiceRequest {
             amount_sats,
             description: description.to_string(),
             expiry_secs,
         };
-        let res = minreq::post(url).with_json(&req).unwrap().send()?;
-        let invoice_str = res.json::<GetInvoiceResponse>()?.invoice;
-        Ok(Bolt11Invoice::from_str(&invoice_str).unwrap())
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        let invoice_str = parse_response::<GetInvoiceResponse>(res)?.invoice;
+        Bolt11Invoice::from_str(&invoice_str)
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+
+    /// Creates an invoice paid through a JIT channel from the node's LSPS2 service, a variable
+    /// amount invoice if `req.amount_msat` is `None`
+    pub fn get_jit_invoice(&self, req: &JitInvoiceRequest) -> Result<Bolt11Invoice, ClientError> {
+        let url = format!("{}/jit-invoice", self.base_url);
+        let res = minreq::post(url).with_json(req)?.send()?;
+        let invoice_str = parse_response::<GetInvoiceResponse>(res)?.invoice;
+        Bolt11Invoice::from_str(&invoice_str)
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+
+    /// Creates an invoice for `payment_hash` whose payments are held until claimed or failed,
+    /// a variable amount invoice if `amount_msat` is `None`
+    pub fn create_hold_invoice(
+        &self,
+        payment_hash: &str,
+        amount_msat: Option<u64>,
+        description: &str,
+        expiry_secs: u32,
+    ) -> Result<Bolt11Invoice, ClientError> {
+        let url = format!("{}/hold-invoices", self.base_url);
+        let req = HoldInvoiceRequest {
+            payment_hash: payment_hash.to_string(),
+            amount_msat,
+            description: description.to_string(),
+            expiry_secs,
+        };
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        let invoice_str = parse_response::<GetInvoiceResponse>(res)?.invoice;
+        Bolt11Invoice::from_str(&invoice_str)
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+
+    pub fn list_held_payments(&self) -> Result<ListHeldPaymentsResponse, ClientError> {
+        let url = format!("{}/hold-invoices/held", self.base_url);
+        parse_response(minreq::get(url).send()?)
+    }
+
+    pub fn claim_hold_invoice(
+        &self,
+        payment_hash: &str,
+        preimage: &str,
+    ) -> Result<(), ClientError> {
+        let url = format!("{}/hold-invoices/{}/claim", self.base_url, payment_hash);
+        let req = ClaimHoldInvoiceRequest {
+            preimage: preimage.to_string(),
+        };
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        parse_response::<serde_json::Value>(res)?;
+        Ok(())
     }
 
-    pub fn sync(&self) -> Result<(), minreq::Error> {
+    pub fn fail_hold_invoice(&self, payment_hash: &str) -> Result<(), ClientError> {
+        let url = format!("{}/hold-invoices/{}/fail", self.base_url, payment_hash);
+        parse_response::<serde_json::Value>(minreq::post(url).send()?)?;
+        Ok(())
+    }
+
+    pub fn get_offer(
+        &self,
+        amount_msat: Option<u64>,
+        description: &str,
+        expiry_secs: Option<u32>,
+        quantity: Option<u64>,
+    ) -> Result<String, ClientError> {
+        let url = format!("{}/get-offer", self.base_url);
+        let req = GetOfferRequest {
+            amount_msat,
+            description: description.to_string(),
+            expiry_secs,
+            quantity,
+        };
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        Ok(parse_response::<GetOfferResponse>(res)?.offer)
+    }
+
+    pub fn pay_offer(
+        &self,
+        offer: &str,
+        amount_msat: Option<u64>,
+        quantity: Option<u64>,
+        payer_note: Option<String>,
+    ) -> Result<String, ClientError> {
+        let url = format!("{}/pay-offer", self.base_url);
+        let req = PayOfferRequest {
+            offer: offer.to_string(),
+            amount_msat,
+            quantity,
+            payer_note,
+        };
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        Ok(parse_response::<PayOfferResponse>(res)?.payment_id)
+    }
+
+    pub fn initiate_refund(
+        &self,
+        amount_msat: u64,
+        expiry_secs: u32,
+        quantity: Option<u64>,
+        payer_note: Option<String>,
+    ) -> Result<String, ClientError> {
+        let url = format!("{}/initiate-refund", self.base_url);
+        let req = InitiateRefundRequest {
+            amount_msat,
+            expiry_secs,
+            quantity,
+            payer_note,
+        };
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        Ok(parse_response::<InitiateRefundResponse>(res)?.refund)
+    }
+
+    pub fn request_refund(&self, refund: &str) -> Result<RequestRefundResponse, ClientError> {
+        let url = format!("{}/request-refund", self.base_url);
+        let req = RequestRefundRequest {
+            refund: refund.to_string(),
+        };
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        parse_response(res)
+    }
+
+    pub fn keysend(
+        &self,
+        pubkey: PublicKey,
+        amount_msat: u64,
+        custom_tlvs: Vec<CustomTlv>,
+    ) -> Result<String, ClientError> {
+        let url = format!("{}/keysend", self.base_url);
+        let req = KeysendRequest {
+            pubkey,
+            amount_msat,
+            custom_tlvs,
+        };
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        Ok(parse_response::<KeysendResponse>(res)?.payment_id)
+    }
+
+    pub fn sync(&self) -> Result<(), ClientError> {
         let url = format!("{}/sync", self.base_url);
-        minreq::post(url).send()?;
+        parse_response::<serde_json::Value>(minreq::post(url).send()?)?;
         Ok(())
     }
 
-    pub fn get_balance(&self) -> Result<GetBalanceResponse, minreq::Error> {
+    /// Mines `count` blocks, paying the rewards to `address` if given, and waits for the node to
+    /// sync them
+    pub fn mine_blocks(
+        &self,
+        count: u64,
+        address: Option<String>,
+    ) -> Result<MineBlocksResponse, ClientError> {
+        let url = format!("{}/chain/mine", self.base_url);
+        let req = MineBlocksRequest { count, address };
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        parse_response(res)
+    }
+
+    /// Replaces the last `req.depth` blocks with a competing chain and waits for the node to
+    /// follow it
+    pub fn reorg(&self, req: &ReorgRequest) -> Result<ReorgResponse, ClientError> {
+        let url = format!("{}/chain/reorg", self.base_url);
+        let res = minreq::post(url).with_json(req)?.send()?;
+        parse_response(res)
+    }
+
+    pub fn chain_tip(&self) -> Result<ChainTip, ClientError> {
+        let url = format!("{}/chain/tip", self.base_url);
+        parse_response(minreq::get(url).send()?)
+    }
+
+    pub fn get_balance(&self) -> Result<GetBalanceResponse, ClientError> {
         let url = format!("{}/balance", self.base_url);
-        minreq::get(url).send()?.json::<GetBalanceResponse>()
+        parse_response(minreq::get(url).send()?)
     }
 
-    pub fn get_payment(&self, payment_hash: &str) -> Result<GetPaymentResponse, minreq::Error> {
+    pub fn get_payment(&self, payment_hash: &str) -> Result<GetPaymentResponse, ClientError> {
         let url = format!("{}/get-payment/{}", self.base_url, payment_hash);
-        minreq::get(url).send()?.json::<GetPaymentResponse>()
+        parse_response(minreq::get(url).send()?)
     }
 
-    pub fn faucet(&self, funding_address: &str) -> Result<(), minreq::Error> {
-        let url = format!("{}/faucet", self.base_url);
-        let req = FaucetRequest {
+    pub fn list_payments(
+        &self,
+        query: &ListPaymentsQuery,
+    ) -> Result<ListPaymentsResponse, ClientError> {
+        let url = format!("{}/payments", self.base_url);
+        let req = with_query(minreq::get(url), query)?;
+        parse_response(req.send()?)
+    }
+
+    pub fn onchain_send(
+        &self,
+        req: &OnchainSendRequest,
+    ) -> Result<OnchainSendResponse, ClientError> {
+        let url = format!("{}/onchain/send", self.base_url);
+        let res = minreq::post(url).with_json(req)?.send()?;
+        parse_response(res)
+    }
+
+    /// Sends 1 BTC to the address and confirms it, returns the txid
+    pub fn faucet(&self, funding_address: &str) -> Result<String, ClientError> {
+        self.faucet_with(&FaucetRequest {
             address: funding_address.to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// Faucet with a custom amount, confirmation count and fee rate, returns the txid
+    pub fn faucet_with(&self, req: &FaucetRequest) -> Result<String, ClientError> {
+        let url = format!("{}/faucet", self.base_url);
+        let res = minreq::post(url).with_json(req)?.send()?;
+        parse_response(res)
+    }
+
+    pub fn get_lsps1_info(&self) -> Result<Lsps1Config, ClientError> {
+        let url = format!("{}/lsps1/info", self.base_url);
+        parse_response(minreq::get(url).send()?)
+    }
+
+    pub fn create_lsps1_order(
+        &self,
+        req: &Lsps1CreateOrderRequest,
+    ) -> Result<Lsps1Order, ClientError> {
+        let url = format!("{}/lsps1/orders", self.base_url);
+        let res = minreq::post(url).with_json(req)?.send()?;
+        parse_response(res)
+    }
+
+    pub fn list_lsps1_orders(&self) -> Result<Lsps1ListOrdersResponse, ClientError> {
+        let url = format!("{}/lsps1/orders", self.base_url);
+        parse_response(minreq::get(url).send()?)
+    }
+
+    pub fn get_lsps1_order(&self, order_id: &str) -> Result<Lsps1Order, ClientError> {
+        let url = format!("{}/lsps1/orders/{}", self.base_url, order_id);
+        parse_response(minreq::get(url).send()?)
+    }
+
+    pub fn set_lsps5_webhook(
+        &self,
+        client_node_id: PublicKey,
+        app_name: &str,
+        webhook: &str,
+    ) -> Result<Lsps5Webhook, ClientError> {
+        let url = format!("{}/lsps5/webhooks", self.base_url);
+        let req = Lsps5SetWebhookRequest {
+            client_node_id,
+            app_name: app_name.to_string(),
+            webhook: webhook.to_string(),
         };
-        let _res = minreq::post(url).with_json(&req).unwrap().send()?;
+        let res = minreq::post(url).with_json(&req)?.send()?;
+        parse_response(res)
+    }
+
+    pub fn list_lsps5_webhooks(&self) -> Result<Lsps5ListWebhooksResponse, ClientError> {
+        let url = format!("{}/lsps5/webhooks", self.base_url);
+        parse_response(minreq::get(url).send()?)
+    }
+
+    pub fn remove_lsps5_webhook(
+        &self,
+        client_node_id: PublicKey,
+        app_name: &str,
+    ) -> Result<Lsps5Webhook, ClientError> {
+        let url = format!(
+            "{}/lsps5/webhooks/{}/{}",
+            self.base_url, client_node_id, app_name
+        );
+        parse_response(minreq::delete(url).send()?)
+    }
+
+    /// Url of the local webhook sink for the given tag, to be registered as a webhook
+    pub fn webhook_sink_url(&self, tag: &str) -> String {
+        format!("{}/webhook-sink/{}", self.base_url, tag)
+    }
+
+    pub fn list_webhook_deliveries(&self) -> Result<ListWebhookDeliveriesResponse, ClientError> {
+        let url = format!("{}/webhook-sink", self.base_url);
+        parse_response(minreq::get(url).send()?)
+    }
+
+    pub fn clear_webhook_deliveries(&self) -> Result<(), ClientError> {
+        let url = format!("{}/webhook-sink", self.base_url);
+        parse_response::<serde_json::Value>(minreq::delete(url).send()?)?;
         Ok(())
     }
+
+    /// Subscribes to the node events, only events emitted after this call are returned
+    pub fn events(&self) -> Result<EventStream, ClientError> {
+        self.event_stream(None)
+    }
+
+    /// Waits for the first event matching `predicate`, failing once `timeout` elapsed
+    ///
+    /// Only events emitted after this call are considered.
+    pub fn wait_for_event(
+        &self,
+        timeout: Duration,
+        mut predicate: impl FnMut(&NodeEvent) -> bool,
+    ) -> Result<NodeEvent, ClientError> {
+        for event in self.event_stream(Some(timeout))? {
+            let event = event?;
+            if predicate(&event) {
+                return Ok(event);
+            }
+        }
+        Err(ClientError::InvalidResponse(
+            "event stream ended".to_string(),
+        ))
+    }
+
+    fn event_stream(&self, timeout: Option<Duration>) -> Result<EventStream, ClientError> {
+        let url = format!("{}/events", self.base_url);
+        let mut req = minreq::get(url).with_header("Accept", "text/event-stream");
+        if let Some(timeout) = timeout {
+            req = req.with_timeout(timeout.as_secs().max(1));
+        }
+        let response = req.send_lazy()?;
+        if response.status_code >= 400 {
+            return Err(ClientError::Api {
+                status: response.status_code,
+                error: ErrorResponse {
+                    code: "http_error".to_string(),
+                    message: response.reason_phrase.clone(),
+                    details: None,
+                },
+            });
+        }
+        Ok(EventStream {
+            response,
+            line: Vec::new(),
+        })
+    }
 }
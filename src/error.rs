@@ -0,0 +1,172 @@
+use std::fmt;
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use electrsd::bitcoind::bitcoincore_rpc;
+use ldk_node::NodeError;
+
+use crate::ErrorResponse;
+
+/// Result type returned by the http api handlers
+pub type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// Error returned by the http api, rendered as a json [ErrorResponse] with a matching status code
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub body: ErrorResponse,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &str, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            body: ErrorResponse {
+                code: code.to_string(),
+                message: message.into(),
+                details: None,
+            },
+        }
+    }
+
+    pub fn bad_request(code: &str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    pub fn not_found(code: &str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, code, message)
+    }
+
+    pub fn internal(code: &str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, code, message)
+    }
+
+    /// Attach the underlying error (or any other context) to the response
+    pub fn with_details(mut self, details: impl fmt::Display) -> Self {
+        self.body.details = Some(details.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}",
+            self.body.code, self.status, self.body.message
+        )
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body)).into_response()
+    }
+}
+
+impl From<NodeError> for ApiError {
+    fn from(e: NodeError) -> Self {
+        let status = match e {
+            NodeError::InvalidAddress
+            | NodeError::InvalidSocketAddress
+            | NodeError::InvalidPublicKey
+            | NodeError::InvalidPaymentId
+            | NodeError::InvalidPaymentHash
+            | NodeError::InvalidPaymentPreimage
+            | NodeError::InvalidAmount
+            | NodeError::InvalidInvoice
+            | NodeError::InvalidOffer
+            | NodeError::InvalidRefund
+            | NodeError::InvalidChannelId
+            | NodeError::InvalidNetwork => StatusCode::BAD_REQUEST,
+            NodeError::DuplicatePayment => StatusCode::CONFLICT,
+            NodeError::InsufficientFunds => StatusCode::UNPROCESSABLE_ENTITY,
+            NodeError::ConnectionFailed => StatusCode::BAD_GATEWAY,
+            NodeError::NotRunning | NodeError::LiquiditySourceUnavailable => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let code = snake_case(&format!("{:?}", e));
+        ApiError::new(status, &code, e.to_string())
+    }
+}
+
+impl From<bitcoincore_rpc::Error> for ApiError {
+    fn from(e: bitcoincore_rpc::Error) -> Self {
+        ApiError::new(
+            StatusCode::BAD_GATEWAY,
+            "bitcoind_rpc_error",
+            "bitcoind rpc call failed",
+        )
+        .with_details(e)
+    }
+}
+
+/// Converts a `CamelCase` variant name into `snake_case`
+fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Error returned by [crate::client::LspsClient]
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request could not be sent or the response could not be read
+    Http(minreq::Error),
+    /// The api answered with an error status
+    Api { status: i32, error: ErrorResponse },
+    /// The api answered successfully but the response could not be interpreted
+    InvalidResponse(String),
+    /// The request could not be built, e.g. its parameters failed to serialize
+    InvalidRequest(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http(e) => write!(f, "http error: {}", e),
+            ClientError::Api { status, error } => {
+                write!(
+                    f,
+                    "api error {} ({}): {}",
+                    error.code, status, error.message
+                )?;
+                if let Some(details) = &error.details {
+                    write!(f, " [{}]", details)?;
+                }
+                Ok(())
+            }
+            ClientError::InvalidResponse(e) => write!(f, "invalid response: {}", e),
+            ClientError::InvalidRequest(e) => write!(f, "invalid request: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<minreq::Error> for ClientError {
+    fn from(e: minreq::Error) -> Self {
+        ClientError::Http(e)
+    }
+}
@@ -0,0 +1,216 @@
+//! Node event processing and the `GET /events` stream.
+//!
+//! A single task drains the node's event queue, records channel and payment state transitions
+//! in the [EventTracker] and fans the events out to subscribers. Handlers must never call
+//! `wait_next_event` themselves, they wait on the tracker instead.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Mutex;
+
+use axum::extract::ws::rejection::WebSocketUpgradeRejection;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use futures::Stream;
+use lspsd::NodeEvent;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::Notify;
+
+use crate::AppState;
+
+/// Number of events kept for slow subscribers before they start missing some
+pub(crate) const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Latest channel and payment events, keyed by user channel id and payment id
+#[derive(Default)]
+pub(crate) struct EventTracker {
+    channels: Mutex<HashMap<u128, NodeEvent>>,
+    payments: Mutex<HashMap<String, NodeEvent>>,
+    notify: Notify,
+}
+
+impl EventTracker {
+    fn record(&self, event: &NodeEvent) {
+        match event {
+            NodeEvent::ChannelPending {
+                user_channel_id, ..
+            }
+            | NodeEvent::ChannelReady {
+                user_channel_id, ..
+            }
+            | NodeEvent::ChannelClosed {
+                user_channel_id, ..
+            } => {
+                let mut channels = self.channels.lock().unwrap();
+                channels.insert(*user_channel_id, event.clone());
+            }
+            NodeEvent::PaymentClaimable { payment_id, .. } => {
+                let mut payments = self.payments.lock().unwrap();
+                payments.insert(payment_id.clone(), event.clone());
+            }
+            NodeEvent::PaymentReceived { payment_id, .. }
+            | NodeEvent::PaymentSuccessful { payment_id, .. }
+            | NodeEvent::PaymentFailed { payment_id, .. } => {
+                if let Some(payment_id) = payment_id {
+                    let mut payments = self.payments.lock().unwrap();
+                    payments.insert(payment_id.clone(), event.clone());
+                }
+            }
+            NodeEvent::PaymentForwarded { .. } => return,
+        }
+        self.notify.notify_waiters();
+    }
+
+    /// Waits until `check` returns a value, re-checking after every recorded event
+    async fn wait_for<T>(&self, mut check: impl FnMut(&Self) -> Option<T>) -> T {
+        loop {
+            // register before checking so an event recorded in between still wakes us up
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(value) = check(self) {
+                return value;
+            }
+            notified.await;
+        }
+    }
+
+    /// Waits for a channel event matching `predicate`, a closed channel always matches
+    pub(crate) async fn wait_for_channel(
+        &self,
+        user_channel_id: u128,
+        predicate: impl Fn(&NodeEvent) -> bool,
+    ) -> NodeEvent {
+        self.wait_for(|tracker| {
+            let channels = tracker.channels.lock().unwrap();
+            channels
+                .get(&user_channel_id)
+                .filter(|event| {
+                    matches!(event, NodeEvent::ChannelClosed { .. }) || predicate(event)
+                })
+                .cloned()
+        })
+        .await
+    }
+
+    /// Closing events of the channels closed since startup
+    pub(crate) fn closed_channels(&self) -> Vec<NodeEvent> {
+        let channels = self.channels.lock().unwrap();
+        channels
+            .values()
+            .filter(|event| matches!(event, NodeEvent::ChannelClosed { .. }))
+            .cloned()
+            .collect()
+    }
+
+    /// Payments to hold invoices that were neither claimed nor failed yet
+    pub(crate) fn held_payments(&self) -> Vec<NodeEvent> {
+        let payments = self.payments.lock().unwrap();
+        payments
+            .values()
+            .filter(|event| matches!(event, NodeEvent::PaymentClaimable { .. }))
+            .cloned()
+            .collect()
+    }
+
+    /// Drops the payment if it is held, failing a held payment emits no event to replace it
+    pub(crate) fn forget_held_payment(&self, payment_id: &str) {
+        let mut payments = self.payments.lock().unwrap();
+        if matches!(
+            payments.get(payment_id),
+            Some(NodeEvent::PaymentClaimable { .. })
+        ) {
+            payments.remove(payment_id);
+        }
+    }
+
+    /// Latest event recorded for the payment, if any
+    pub(crate) fn payment_event(&self, payment_id: &str) -> Option<NodeEvent> {
+        self.payments.lock().unwrap().get(payment_id).cloned()
+    }
+
+    /// Waits for the payment to be received, to succeed or to fail
+    pub(crate) async fn wait_for_payment(&self, payment_id: &str) -> NodeEvent {
+        self.wait_for(|tracker| {
+            tracker
+                .payment_event(payment_id)
+                .filter(|event| !matches!(event, NodeEvent::PaymentClaimable { .. }))
+        })
+        .await
+    }
+}
+
+/// Drains the node's event queue, records and publishes every event to the subscribers
+pub(crate) async fn process_events(state: AppState) {
+    loop {
+        let event = state.node.next_event_async().await;
+
+        if let Some(node_event) = NodeEvent::from_event(&event) {
+            state.tracker.record(&node_event);
+            // an error only means nobody is subscribed right now
+            let _ = state.events.send(node_event);
+        }
+
+        if let Err(e) = state.node.event_handled() {
+            println!("failed to handle event: {}", e);
+        }
+    }
+}
+
+/// Next event for the subscriber, skipping over the ones it was too slow to receive
+async fn next_event(events: &mut Receiver<NodeEvent>) -> Option<NodeEvent> {
+    loop {
+        match events.recv().await {
+            Ok(event) => return Some(event),
+            Err(RecvError::Lagged(missed)) => {
+                println!("event subscriber lagged, missed {} events", missed);
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+fn sse_stream(events: Receiver<NodeEvent>) -> impl Stream<Item = Result<SseEvent, Infallible>> {
+    futures::stream::unfold(events, |mut events| async move {
+        let event = next_event(&mut events).await?;
+        let sse_event = SseEvent::default()
+            .json_data(&event)
+            .unwrap_or_else(|e| SseEvent::default().comment(e.to_string()));
+        Some((Ok(sse_event), events))
+    })
+}
+
+async fn stream_websocket(mut socket: WebSocket, mut events: Receiver<NodeEvent>) {
+    while let Some(event) = next_event(&mut events).await {
+        let json = match serde_json::to_string(&event) {
+            Ok(json) => json,
+            Err(e) => {
+                println!("failed to serialize event: {}", e);
+                continue;
+            }
+        };
+        if socket.send(Message::Text(json.into())).await.is_err() {
+            // the client went away
+            break;
+        }
+    }
+}
+
+/// Streams node events as a websocket if the client asks for an upgrade, as server-sent events
+/// otherwise
+pub(crate) async fn events_handler(
+    State(state): State<AppState>,
+    ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> Response {
+    let events = state.events.subscribe();
+    match ws {
+        Ok(ws) => ws.on_upgrade(move |socket| stream_websocket(socket, events)),
+        Err(_) => Sse::new(sse_stream(events))
+            .keep_alive(KeepAlive::default())
+            .into_response(),
+    }
+}
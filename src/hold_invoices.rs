@@ -0,0 +1,155 @@
+//! Hold invoices, whose payments stay pending until they are explicitly claimed or failed.
+//!
+//! Payments to invoices created for a caller supplied payment hash surface as `PaymentClaimable`
+//! events, which the event tracker keeps. Failing a payment or letting its claim deadline pass
+//! emits no event, so a tracked payment only counts as held while the node still has it pending
+//! and its deadline is not reached.
+
+use axum::extract::{Path, State};
+use axum::Json;
+use hex::FromHex;
+use ldk_node::lightning::ln::channelmanager::PaymentId;
+use ldk_node::lightning::types::payment::{PaymentHash, PaymentPreimage};
+use ldk_node::lightning_invoice::{Bolt11InvoiceDescription, Description};
+use ldk_node::payment::PaymentStatus;
+use lspsd::error::{ApiError, ApiResult};
+use lspsd::{
+    ClaimHoldInvoiceRequest, GetInvoiceResponse, HeldPayment, HoldInvoiceRequest,
+    ListHeldPaymentsResponse, NodeEvent,
+};
+use serde_json::{json, Value};
+
+use crate::AppState;
+
+fn parse_payment_hash(payment_hash: &str) -> Result<PaymentHash, ApiError> {
+    let bytes = <[u8; 32]>::from_hex(payment_hash).map_err(|e| {
+        ApiError::bad_request(
+            "invalid_payment_hash",
+            "payment hash must be 32 bytes of hex",
+        )
+        .with_details(e)
+    })?;
+    Ok(PaymentHash(bytes))
+}
+
+fn held_payment(event: NodeEvent) -> Option<HeldPayment> {
+    match event {
+        NodeEvent::PaymentClaimable {
+            payment_id,
+            payment_hash,
+            claimable_amount_msat,
+            claim_deadline,
+        } => Some(HeldPayment {
+            payment_id,
+            payment_hash,
+            claimable_amount_msat,
+            claim_deadline,
+        }),
+        _ => None,
+    }
+}
+
+/// Whether the node can still claim or fail the payment
+fn is_still_held(state: &AppState, payment: &HeldPayment) -> bool {
+    let Ok(payment_id) = <[u8; 32]>::from_hex(&payment.payment_id) else {
+        return false;
+    };
+    let pending = state
+        .node
+        .payment(&PaymentId(payment_id))
+        .is_some_and(|details| details.status == PaymentStatus::Pending);
+    let height = state.node.status().current_best_block.height;
+    pending
+        && payment
+            .claim_deadline
+            .is_none_or(|deadline| height < deadline)
+}
+
+pub(crate) async fn create_hold_invoice(
+    State(state): State<AppState>,
+    Json(req): Json<HoldInvoiceRequest>,
+) -> ApiResult<GetInvoiceResponse> {
+    let payment_hash = parse_payment_hash(&req.payment_hash)?;
+    let description = Description::new(req.description).map_err(|e| {
+        ApiError::bad_request("invalid_description", "invalid invoice description").with_details(e)
+    })?;
+    let description = Bolt11InvoiceDescription::Direct(description);
+
+    let bolt11_payment = state.node.bolt11_payment();
+    let invoice = match req.amount_msat {
+        Some(amount_msat) => bolt11_payment.receive_for_hash(
+            amount_msat,
+            &description,
+            req.expiry_secs,
+            payment_hash,
+        )?,
+        None => bolt11_payment.receive_variable_amount_for_hash(
+            &description,
+            req.expiry_secs,
+            payment_hash,
+        )?,
+    };
+
+    Ok(Json(GetInvoiceResponse {
+        invoice: invoice.to_string(),
+    }))
+}
+
+pub(crate) async fn list_held_payments(
+    State(state): State<AppState>,
+) -> ApiResult<ListHeldPaymentsResponse> {
+    let payments = state
+        .tracker
+        .held_payments()
+        .into_iter()
+        .filter_map(held_payment)
+        .filter(|payment| is_still_held(&state, payment))
+        .collect();
+
+    Ok(Json(ListHeldPaymentsResponse { payments }))
+}
+
+pub(crate) async fn claim_hold_invoice(
+    State(state): State<AppState>,
+    Path(payment_hash): Path<String>,
+    Json(req): Json<ClaimHoldInvoiceRequest>,
+) -> ApiResult<Value> {
+    let hash = parse_payment_hash(&payment_hash)?;
+    let preimage = <[u8; 32]>::from_hex(&req.preimage).map_err(|e| {
+        ApiError::bad_request("invalid_preimage", "preimage must be 32 bytes of hex")
+            .with_details(e)
+    })?;
+
+    // inbound bolt11 payments are identified by their payment hash
+    let held = state
+        .tracker
+        .payment_event(&payment_hash.to_lowercase())
+        .and_then(held_payment)
+        .filter(|payment| is_still_held(&state, payment))
+        .ok_or_else(|| {
+            ApiError::not_found(
+                "payment_not_held",
+                format!("no held payment for hash {}", payment_hash),
+            )
+        })?;
+
+    state.node.bolt11_payment().claim_for_hash(
+        hash,
+        held.claimable_amount_msat,
+        PaymentPreimage(preimage),
+    )?;
+    Ok(Json(json!({"claimed": true})))
+}
+
+pub(crate) async fn fail_hold_invoice(
+    State(state): State<AppState>,
+    Path(payment_hash): Path<String>,
+) -> ApiResult<Value> {
+    let hash = parse_payment_hash(&payment_hash)?;
+    state.node.bolt11_payment().fail_for_hash(hash)?;
+    // failing emits no event, forget the payment so it is no longer listed as held
+    state
+        .tracker
+        .forget_held_payment(&payment_hash.to_lowercase());
+    Ok(Json(json!({"failed": true})))
+}
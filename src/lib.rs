@@ -1,10 +1,17 @@
 pub mod client;
+pub mod error;
 pub mod utils;
 mod versions;
 
 use anyhow::Context;
+use hex::DisplayHex;
 use ldk_node::bitcoin::secp256k1::PublicKey;
-use ldk_node::ChannelDetails;
+use ldk_node::config::{ChannelConfig, MaxDustHTLCExposure};
+use ldk_node::payment::PaymentDetails;
+use ldk_node::{
+    BalanceDetails, ChannelDetails, CustomTlvRecord, Event, LightningBalance, PeerDetails,
+    PendingSweepBalance,
+};
 use log::{debug, error, warn};
 use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
@@ -25,7 +32,241 @@ use crate::client::LspsClient;
 pub struct LspConfig {
     pub pubkey: PublicKey,
     pub ip_port: String,
+    /// Token to use in LSPS2 requests, set when the node runs in token-required mode
     pub token: Option<String>,
+    /// LSPS2 service parameters in effect on the node
+    pub lsps2: Lsps2Config,
+    /// LSPS1 service parameters, if the LSPS1 service is enabled
+    pub lsps1: Option<Lsps1Config>,
+    /// Peers whose channels the node accepts as zero-conf
+    #[serde(default)]
+    pub trusted_peers_0conf: Vec<PublicKey>,
+}
+
+/// Parameters of the LSPS2 (JIT channel) service, see `ldk_node::liquidity::LSPS2ServiceConfig`
+///
+/// Missing fields fall back to the [Default] values when deserialized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lsps2Config {
+    /// Token clients must provide in `get_info` requests, if any
+    ///
+    /// Requests with a missing or different token are rejected by the node.
+    pub require_token: Option<String>,
+    /// Whether the service is advertised in the node announcement
+    pub advertise_service: bool,
+    /// Proportional fee charged for opening a channel
+    pub channel_opening_fee_ppm: u32,
+    /// Proportional amount added on top of the payment size when sizing the channel
+    pub channel_over_provisioning_ppm: u32,
+    /// Minimum fee charged for opening a channel
+    pub min_channel_opening_fee_msat: u64,
+    /// Number of blocks the channel is guaranteed to stay open
+    pub min_channel_lifetime: u32,
+    /// Maximum `to_self_delay` the client may request
+    pub max_client_to_self_delay: u32,
+    /// Smallest payment that can trigger a JIT channel
+    pub min_payment_size_msat: u64,
+    /// Largest payment that can trigger a JIT channel
+    pub max_payment_size_msat: u64,
+}
+
+impl Default for Lsps2Config {
+    fn default() -> Self {
+        Lsps2Config {
+            require_token: None,
+            advertise_service: true,
+            channel_opening_fee_ppm: 0,
+            channel_over_provisioning_ppm: 500_000,
+            min_channel_opening_fee_msat: 0,
+            min_channel_lifetime: 100,
+            max_client_to_self_delay: 72,
+            min_payment_size_msat: 1000,
+            max_payment_size_msat: 100_000_000_000,
+        }
+    }
+}
+
+impl From<Lsps2Config> for ldk_node::liquidity::LSPS2ServiceConfig {
+    fn from(config: Lsps2Config) -> Self {
+        Self {
+            require_token: config.require_token,
+            advertise_service: config.advertise_service,
+            channel_opening_fee_ppm: config.channel_opening_fee_ppm,
+            channel_over_provisioning_ppm: config.channel_over_provisioning_ppm,
+            min_channel_opening_fee_msat: config.min_channel_opening_fee_msat,
+            min_channel_lifetime: config.min_channel_lifetime,
+            max_client_to_self_delay: config.max_client_to_self_delay,
+            min_payment_size_msat: config.min_payment_size_msat,
+            max_payment_size_msat: config.max_payment_size_msat,
+        }
+    }
+}
+
+/// Parameters of the LSPS1 (channel request) service
+///
+/// Orders are placed through the http api, the node opens the channel once the order is paid.
+/// Missing fields fall back to the [Default] values when deserialized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lsps1Config {
+    /// Smallest inbound liquidity a client may order
+    pub min_initial_lsp_balance_sat: u64,
+    /// Largest inbound liquidity a client may order
+    pub max_initial_lsp_balance_sat: u64,
+    /// Smallest balance a client may buy on its side of the channel
+    pub min_initial_client_balance_sat: u64,
+    /// Largest balance a client may buy on its side of the channel
+    pub max_initial_client_balance_sat: u64,
+    /// Longest channel lifetime a client may order, in blocks
+    pub max_channel_expiry_blocks: u32,
+    /// Fixed part of the order fee
+    pub fee_base_sat: u64,
+    /// Proportional part of the order fee, applied to the channel size
+    pub fee_ppm: u32,
+    /// How long an order can be paid for, in seconds
+    pub order_expiry_secs: u32,
+}
+
+impl Default for Lsps1Config {
+    fn default() -> Self {
+        Lsps1Config {
+            min_initial_lsp_balance_sat: 10_000,
+            max_initial_lsp_balance_sat: 16_000_000,
+            min_initial_client_balance_sat: 0,
+            max_initial_client_balance_sat: 8_000_000,
+            max_channel_expiry_blocks: 12_960,
+            fee_base_sat: 0,
+            fee_ppm: 0,
+            order_expiry_secs: 3600,
+        }
+    }
+}
+
+impl Lsps1Config {
+    /// Fee charged for a channel with the given balances
+    pub fn fee_sat(&self, lsp_balance_sat: u64, client_balance_sat: u64) -> u64 {
+        let channel_size_sat = lsp_balance_sat + client_balance_sat;
+        self.fee_base_sat + channel_size_sat * self.fee_ppm as u64 / 1_000_000
+    }
+}
+
+/// Contents of the json file passed to the daemon with `--config-file`
+///
+/// Options given on the command line take precedence over the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigFile {
+    pub lsps2: Lsps2Config,
+    /// Enables the LSPS1 service when set
+    pub lsps1: Option<Lsps1Config>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lsps1CreateOrderRequest {
+    pub client_node_id: PublicKey,
+    /// Where to reach the client, only needed if it is not already connected to the node
+    pub client_address: Option<String>,
+    pub lsp_balance_sat: u64,
+    pub client_balance_sat: u64,
+    pub channel_expiry_blocks: u32,
+    /// Where to refund the payment if the channel can't be opened
+    #[serde(default)]
+    pub refund_onchain_address: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Lsps1OrderState {
+    Created,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Lsps1PaymentState {
+    ExpectPayment,
+    Paid,
+    Refunded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lsps1Payment {
+    pub state: Lsps1PaymentState,
+    pub fee_total_sat: u64,
+    pub order_total_sat: u64,
+    pub bolt11_invoice: String,
+    pub payment_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lsps1Channel {
+    pub funded_at: u64,
+    pub user_channel_id: u128,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lsps1Order {
+    pub order_id: String,
+    pub client_node_id: PublicKey,
+    pub lsp_balance_sat: u64,
+    pub client_balance_sat: u64,
+    pub channel_expiry_blocks: u32,
+    /// Unix timestamp in seconds
+    pub created_at: u64,
+    /// Unix timestamp in seconds after which the order can no longer be paid
+    pub expires_at: u64,
+    pub order_state: Lsps1OrderState,
+    pub payment: Lsps1Payment,
+    pub channel: Option<Lsps1Channel>,
+    pub failure_reason: Option<String>,
+    pub refund_onchain_address: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lsps1ListOrdersResponse {
+    pub orders: Vec<Lsps1Order>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lsps5SetWebhookRequest {
+    pub client_node_id: PublicKey,
+    pub app_name: String,
+    pub webhook: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lsps5Webhook {
+    pub client_node_id: PublicKey,
+    pub app_name: String,
+    pub webhook: String,
+    /// Unix timestamp in seconds
+    pub registered_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lsps5ListWebhooksResponse {
+    pub webhooks: Vec<Lsps5Webhook>,
+}
+
+/// A notification received by the local webhook sink
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    /// Last path segment the notification was posted to, e.g. `alice` for `/webhook-sink/alice`
+    pub tag: String,
+    /// Unix timestamp in seconds
+    pub received_at: u64,
+    /// Value of the `x-lsps5-timestamp` header
+    pub timestamp: Option<String>,
+    /// Value of the `x-lsps5-signature` header
+    pub signature: Option<String>,
+    pub body: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListWebhookDeliveriesResponse {
+    pub deliveries: Vec<WebhookDelivery>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,33 +274,222 @@ pub struct FundingAddress {
     pub address: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FaucetRequest {
     pub address: String,
+    /// Amount to send, 1 BTC if not set
+    #[serde(default)]
+    pub amount_sats: Option<u64>,
+    /// Blocks to mine after sending, 1 if not set, 0 leaves the funds unconfirmed in the mempool
+    #[serde(default)]
+    pub confirmations: Option<u64>,
+    /// Fee rate in sat/vB, estimated if not set
+    #[serde(default)]
+    pub fee_rate_sat_per_vb: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MineBlocksRequest {
+    pub count: u64,
+    /// Address receiving the block rewards, a bitcoind wallet address if not set
+    #[serde(default)]
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MineBlocksResponse {
+    pub block_hashes: Vec<String>,
+    /// Tip of the node once synced to the new blocks
+    pub tip: ChainTip,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReorgRequest {
+    /// Number of blocks to disconnect from the tip
+    pub depth: u64,
+    /// Length of the competing chain, `depth + 1` if not set so it outgrows the old one
+    #[serde(default)]
+    pub mine_blocks: Option<u64>,
+    /// Mine only these mempool transactions in the first competing block, leaving the others
+    /// unconfirmed, all mempool transactions are mined if not set. They must be in the mempool or
+    /// in the disconnected blocks, and the competing chain at least one block long
+    #[serde(default)]
+    pub include_txids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReorgResponse {
+    /// First disconnected block
+    pub invalidated_block_hash: String,
+    /// Blocks of the competing chain
+    pub block_hashes: Vec<String>,
+    /// Tip of the node once synced to the competing chain
+    pub tip: ChainTip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainTip {
+    pub height: u32,
+    pub block_hash: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OnchainSendRequest {
+    pub address: String,
+    /// Amount to send, required unless `send_all` is set
+    #[serde(default)]
+    pub amount_sats: Option<u64>,
+    /// Send the whole wallet balance instead of `amount_sats`
+    #[serde(default)]
+    pub send_all: bool,
+    /// Keep the reserve needed to bump anchor channel closes, only used with `send_all`
+    #[serde(default)]
+    pub retain_reserve: bool,
+    /// Fee rate in sat/vB, estimated by the node if not set
+    #[serde(default)]
+    pub fee_rate_sat_per_vb: Option<u64>,
+    /// Blocks to mine after sending, regtest only
+    #[serde(default)]
+    pub mine_blocks: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnchainSendResponse {
+    pub txid: String,
+    /// Fee paid by the transaction, unset if the wallet sync didn't see the transaction yet
+    pub fee_sats: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenChannelRequest {
     pub pubkey: PublicKey,
+    /// `host:port` or `pubkey@host:port` of the peer
     pub ip_port: String,
     pub funding_sats: u64,
     pub push_sats: u64,
+    /// Wait for the channel before returning, or return as soon as it is initiated.
+    ///
+    /// Waiting returns once the channel is ready when the node mined at least the confirmations
+    /// the peer requires, or when `zero_conf` is set. Otherwise, including when the node doesn't
+    /// run its own bitcoind, it only returns once the channel is pending, check
+    /// `GET /channels/{user_channel_id}` for readiness.
+    #[serde(default = "default_wait")]
+    pub wait: bool,
+    /// Blocks to mine once the funding transaction is broadcast, regtest only
+    #[serde(default = "default_channel_confirmations")]
+    pub confirmations: u64,
+    /// Forwarding config of the channel, ldk's defaults for the fields that aren't set
+    #[serde(default)]
+    pub channel_config: Option<ChannelConfigUpdate>,
+    /// Announce the channel to the network, requires the node alias and announcement addresses
+    #[serde(default)]
+    pub announce: bool,
+    /// Wait for the channel to be ready without mining, `confirmations` is ignored. The peer must
+    /// trust us for zero-conf channels, the payer node spawned on regtest only does with
+    /// `--payer-trusts-lsp-0conf`
+    #[serde(default)]
+    pub zero_conf: bool,
+}
+
+fn default_wait() -> bool {
+    true
 }
+
+fn default_channel_confirmations() -> u64 {
+    6
+}
+
+/// Forwarding config of a channel, the fields that aren't set are left unchanged
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChannelConfigUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forwarding_fee_base_msat: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forwarding_fee_proportional_millionths: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cltv_expiry_delta: Option<u16>,
+    /// Fixed limit on the dust HTLCs in flight
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_dust_htlc_exposure_msat: Option<u64>,
+}
+
+impl ChannelConfigUpdate {
+    pub fn apply(&self, config: &mut ChannelConfig) {
+        if let Some(base_msat) = self.forwarding_fee_base_msat {
+            config.forwarding_fee_base_msat = base_msat;
+        }
+        if let Some(ppm) = self.forwarding_fee_proportional_millionths {
+            config.forwarding_fee_proportional_millionths = ppm;
+        }
+        if let Some(delta) = self.cltv_expiry_delta {
+            config.cltv_expiry_delta = delta;
+        }
+        if let Some(limit_msat) = self.max_dust_htlc_exposure_msat {
+            config.max_dust_htlc_exposure = MaxDustHTLCExposure::FixedLimit { limit_msat };
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenChannelResponse {
     pub user_channel_id: u128,
+    /// Funding outpoint as `txid:vout`, not known yet when not waiting
+    pub funding_txo: Option<String>,
+}
+
+/// Lifecycle state of a channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelState {
+    /// Being negotiated with the counterparty, the funding transaction isn't broadcast yet
+    Pending,
+    /// The funding transaction is broadcast and waits for enough confirmations
+    AwaitingConfirmations,
+    Ready,
+    /// A close was initiated or the channel is closed but not all funds are swept yet
+    Closing,
+    /// The channel is closed and all of its funds are swept
+    Closed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelStatus {
+    pub user_channel_id: u128,
+    pub state: ChannelState,
+    pub channel_id: Option<String>,
+    pub counterparty_node_id: Option<PublicKey>,
+    pub funding_txo: Option<String>,
+    pub confirmations: Option<u32>,
+    pub confirmations_required: Option<u32>,
+    pub closure_reason: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompactChannel {
     pub channel_id: String,
     pub counterparty_node_id: PublicKey,
+    /// `txid:vout` of the funding output, once the funding transaction is created
+    pub funding_txo: Option<String>,
+    pub short_channel_id: Option<u64>,
     pub channel_value_sats: u64,
     pub user_channel_id: u128,
     pub outbound_capacity_msat: u64,
     pub inbound_capacity_msat: u64,
+    /// Largest HTLC we can send right now
+    pub next_outbound_htlc_limit_msat: u64,
+    pub confirmations_required: Option<u32>,
+    pub confirmations: Option<u32>,
+    pub is_outbound: bool,
+    pub is_announced: bool,
     pub is_channel_ready: bool,
     pub is_usable: bool,
+    pub forwarding_fee_base_msat: u32,
+    pub forwarding_fee_proportional_millionths: u32,
+    pub cltv_expiry_delta: Option<u16>,
+    /// Reserve the counterparty has to keep in the channel
+    pub counterparty_unspendable_punishment_reserve: u64,
+    /// Reserve we have to keep in the channel, unknown until the channel is accepted
+    pub unspendable_punishment_reserve: Option<u64>,
 }
 
 impl From<ChannelDetails> for CompactChannel {
@@ -67,21 +497,144 @@ impl From<ChannelDetails> for CompactChannel {
         Self {
             channel_id: channel.channel_id.to_string(),
             counterparty_node_id: channel.counterparty_node_id,
+            funding_txo: channel.funding_txo.map(|txo| txo.to_string()),
+            short_channel_id: channel.short_channel_id,
             channel_value_sats: channel.channel_value_sats,
             user_channel_id: channel.user_channel_id.0,
             outbound_capacity_msat: channel.outbound_capacity_msat,
             inbound_capacity_msat: channel.inbound_capacity_msat,
+            next_outbound_htlc_limit_msat: channel.next_outbound_htlc_limit_msat,
+            confirmations_required: channel.confirmations_required,
+            confirmations: channel.confirmations,
+            is_outbound: channel.is_outbound,
+            is_announced: channel.is_announced,
             is_channel_ready: channel.is_channel_ready,
             is_usable: channel.is_usable,
+            forwarding_fee_base_msat: channel.config.forwarding_fee_base_msat,
+            forwarding_fee_proportional_millionths: channel
+                .config
+                .forwarding_fee_proportional_millionths,
+            cltv_expiry_delta: channel.cltv_expiry_delta,
+            counterparty_unspendable_punishment_reserve: channel
+                .counterparty_unspendable_punishment_reserve,
+            unspendable_punishment_reserve: channel.unspendable_punishment_reserve,
         }
     }
 }
 
+/// Filters of `GET /channels`, all filters are optional
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListChannelsQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counterparty_node_id: Option<PublicKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_channel_ready: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_usable: Option<bool>,
+}
+
+impl ListChannelsQuery {
+    pub fn matches(&self, channel: &CompactChannel) -> bool {
+        self.counterparty_node_id
+            .is_none_or(|id| id == channel.counterparty_node_id)
+            && self
+                .is_channel_ready
+                .is_none_or(|ready| ready == channel.is_channel_ready)
+            && self
+                .is_usable
+                .is_none_or(|usable| usable == channel.is_usable)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListChannelsResponse {
     pub channels: Vec<CompactChannel>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectPeerRequest {
+    /// Optional when `address` is a `pubkey@host:port` uri
+    #[serde(default)]
+    pub pubkey: Option<PublicKey>,
+    /// `host:port` or `pubkey@host:port` of the peer
+    pub address: String,
+    /// Reconnect to the peer on restart
+    #[serde(default)]
+    pub persist: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Peer {
+    pub node_id: PublicKey,
+    pub address: String,
+    pub is_persisted: bool,
+    pub is_connected: bool,
+}
+
+impl From<PeerDetails> for Peer {
+    fn from(peer: PeerDetails) -> Self {
+        Self {
+            node_id: peer.node_id,
+            address: peer.address.to_string(),
+            is_persisted: peer.is_persisted,
+            is_connected: peer.is_connected,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListPeersResponse {
+    pub peers: Vec<Peer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloseChannelRequest {
+    pub counterparty_node_id: PublicKey,
+    /// Reason given to the counterparty, only used when force closing
+    #[serde(default)]
+    pub force_close_reason: Option<String>,
+    /// Keep mining blocks until all of our funds from the channel are swept, regtest only
+    #[serde(default)]
+    pub mine_until_swept: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloseChannelResponse {
+    pub user_channel_id: u128,
+    /// Number of blocks mined while waiting for the funds to be swept
+    pub blocks_mined: u64,
+}
+
+/// Progress of the claims on a closed channel's funds, from the least to the most advanced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SweepStatus {
+    /// Funds are still claimable by the channel monitor, e.g. waiting for a timelock
+    AwaitingClaim,
+    /// Funds are handed to the sweeper but the sweeping transaction wasn't broadcast yet
+    PendingBroadcast,
+    /// The sweeping transaction is waiting for enough confirmations
+    AwaitingConfirmation,
+    /// Nothing is left to claim
+    Swept,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClosedChannel {
+    pub channel_id: String,
+    pub user_channel_id: u128,
+    pub counterparty_node_id: Option<PublicKey>,
+    pub reason: Option<String>,
+    pub sweep_status: SweepStatus,
+    /// Amount still waiting to be swept to the onchain wallet
+    pub pending_sweep_sats: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListClosedChannelsResponse {
+    pub channels: Vec<ClosedChannel>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PayInvoiceRequest {
     pub invoice: String,
@@ -104,15 +657,716 @@ pub struct GetInvoiceResponse {
     pub invoice: String,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JitInvoiceRequest {
+    /// Amount of the invoice, a variable amount invoice is created if not set
+    #[serde(default)]
+    pub amount_msat: Option<u64>,
+    pub description: String,
+    pub expiry_secs: u32,
+    /// Maximum total fee the LSP may charge, only used with a fixed amount
+    #[serde(default)]
+    pub max_total_lsp_fee_limit_msat: Option<u64>,
+    /// Maximum proportional fee the LSP may charge, only used with a variable amount
+    #[serde(default)]
+    pub max_proportional_lsp_fee_limit_ppm_msat: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HoldInvoiceRequest {
+    /// Hex encoded payment hash, the caller keeps the preimage until it claims the payment
+    pub payment_hash: String,
+    /// Amount of the invoice, a variable amount invoice is created if not set
+    #[serde(default)]
+    pub amount_msat: Option<u64>,
+    pub description: String,
+    pub expiry_secs: u32,
+}
+
+/// Payment to a hold invoice waiting to be claimed or failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeldPayment {
+    pub payment_id: String,
+    pub payment_hash: String,
+    pub claimable_amount_msat: u64,
+    /// Block height by which the payment must be claimed, or it is failed back
+    pub claim_deadline: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListHeldPaymentsResponse {
+    pub payments: Vec<HeldPayment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimHoldInvoiceRequest {
+    /// Hex encoded preimage of the invoice's payment hash
+    pub preimage: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetOfferRequest {
+    /// Leave empty for a variable amount offer
+    pub amount_msat: Option<u64>,
+    pub description: String,
+    pub expiry_secs: Option<u32>,
+    /// Maximum quantity of items per payment, only used for fixed amount offers
+    pub quantity: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetOfferResponse {
+    pub offer: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayOfferRequest {
+    pub offer: String,
+    /// Required for variable amount offers
+    pub amount_msat: Option<u64>,
+    pub quantity: Option<u64>,
+    pub payer_note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayOfferResponse {
+    pub payment_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InitiateRefundRequest {
+    pub amount_msat: u64,
+    pub expiry_secs: u32,
+    pub quantity: Option<u64>,
+    pub payer_note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InitiateRefundResponse {
+    pub refund: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestRefundRequest {
+    pub refund: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestRefundResponse {
+    pub payment_hash: String,
+    pub amount_msat: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetBalanceResponse {
     pub total_onchain_balance_sats: u64,
     pub spendable_onchain_balance_sats: u64,
+    /// Onchain funds kept back to bump anchor channel closes
+    pub total_anchor_channels_reserve_sats: u64,
+    pub total_lightning_balance_sats: u64,
+    /// Balances held by the channel monitors, including the ones of closed channels
+    pub lightning_balances: Vec<ChannelBalance>,
+    /// Funds of closed channels on their way to the onchain wallet
+    pub pending_balances_from_channel_closures: Vec<PendingSweep>,
+}
+
+impl From<&BalanceDetails> for GetBalanceResponse {
+    fn from(balances: &BalanceDetails) -> Self {
+        Self {
+            total_onchain_balance_sats: balances.total_onchain_balance_sats,
+            spendable_onchain_balance_sats: balances.spendable_onchain_balance_sats,
+            total_anchor_channels_reserve_sats: balances.total_anchor_channels_reserve_sats,
+            total_lightning_balance_sats: balances.total_lightning_balance_sats,
+            lightning_balances: balances
+                .lightning_balances
+                .iter()
+                .map(ChannelBalance::from)
+                .collect(),
+            pending_balances_from_channel_closures: balances
+                .pending_balances_from_channel_closures
+                .iter()
+                .map(PendingSweep::from)
+                .collect(),
+        }
+    }
+}
+
+/// Balance held by a channel monitor, see `ldk_node::LightningBalance`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChannelBalance {
+    /// Our balance if the channel was closed now
+    ClaimableOnChannelClose {
+        channel_id: String,
+        counterparty_node_id: PublicKey,
+        amount_sats: u64,
+        transaction_fee_sats: u64,
+    },
+    /// Claimed by a transaction that waits for enough confirmations
+    ClaimableAwaitingConfirmations {
+        channel_id: String,
+        counterparty_node_id: PublicKey,
+        amount_sats: u64,
+        confirmation_height: u32,
+    },
+    /// HTLC we know the preimage of, the counterparty may claim it after `timeout_height`
+    ContentiousClaimable {
+        channel_id: String,
+        counterparty_node_id: PublicKey,
+        amount_sats: u64,
+        timeout_height: u32,
+        payment_hash: String,
+    },
+    /// Outbound HTLC we can claim back after `claimable_height`
+    MaybeTimeoutClaimableHtlc {
+        channel_id: String,
+        counterparty_node_id: PublicKey,
+        amount_sats: u64,
+        claimable_height: u32,
+        payment_hash: String,
+        outbound_payment: bool,
+    },
+    /// Inbound HTLC we can claim if we learn the preimage before `expiry_height`
+    MaybePreimageClaimableHtlc {
+        channel_id: String,
+        counterparty_node_id: PublicKey,
+        amount_sats: u64,
+        expiry_height: u32,
+        payment_hash: String,
+    },
+    /// Output of a revoked counterparty commitment we can claim
+    CounterpartyRevokedOutputClaimable {
+        channel_id: String,
+        counterparty_node_id: PublicKey,
+        amount_sats: u64,
+    },
+}
+
+impl ChannelBalance {
+    pub fn channel_id(&self) -> &str {
+        match self {
+            ChannelBalance::ClaimableOnChannelClose { channel_id, .. }
+            | ChannelBalance::ClaimableAwaitingConfirmations { channel_id, .. }
+            | ChannelBalance::ContentiousClaimable { channel_id, .. }
+            | ChannelBalance::MaybeTimeoutClaimableHtlc { channel_id, .. }
+            | ChannelBalance::MaybePreimageClaimableHtlc { channel_id, .. }
+            | ChannelBalance::CounterpartyRevokedOutputClaimable { channel_id, .. } => channel_id,
+        }
+    }
+
+    pub fn amount_sats(&self) -> u64 {
+        match self {
+            ChannelBalance::ClaimableOnChannelClose { amount_sats, .. }
+            | ChannelBalance::ClaimableAwaitingConfirmations { amount_sats, .. }
+            | ChannelBalance::ContentiousClaimable { amount_sats, .. }
+            | ChannelBalance::MaybeTimeoutClaimableHtlc { amount_sats, .. }
+            | ChannelBalance::MaybePreimageClaimableHtlc { amount_sats, .. }
+            | ChannelBalance::CounterpartyRevokedOutputClaimable { amount_sats, .. } => {
+                *amount_sats
+            }
+        }
+    }
+}
+
+impl From<&LightningBalance> for ChannelBalance {
+    fn from(balance: &LightningBalance) -> Self {
+        match balance {
+            LightningBalance::ClaimableOnChannelClose {
+                channel_id,
+                counterparty_node_id,
+                amount_satoshis,
+                transaction_fee_satoshis,
+                ..
+            } => ChannelBalance::ClaimableOnChannelClose {
+                channel_id: channel_id.to_string(),
+                counterparty_node_id: *counterparty_node_id,
+                amount_sats: *amount_satoshis,
+                transaction_fee_sats: *transaction_fee_satoshis,
+            },
+            LightningBalance::ClaimableAwaitingConfirmations {
+                channel_id,
+                counterparty_node_id,
+                amount_satoshis,
+                confirmation_height,
+                ..
+            } => ChannelBalance::ClaimableAwaitingConfirmations {
+                channel_id: channel_id.to_string(),
+                counterparty_node_id: *counterparty_node_id,
+                amount_sats: *amount_satoshis,
+                confirmation_height: *confirmation_height,
+            },
+            LightningBalance::ContentiousClaimable {
+                channel_id,
+                counterparty_node_id,
+                amount_satoshis,
+                timeout_height,
+                payment_hash,
+                ..
+            } => ChannelBalance::ContentiousClaimable {
+                channel_id: channel_id.to_string(),
+                counterparty_node_id: *counterparty_node_id,
+                amount_sats: *amount_satoshis,
+                timeout_height: *timeout_height,
+                payment_hash: payment_hash.0.to_lower_hex_string(),
+            },
+            LightningBalance::MaybeTimeoutClaimableHTLC {
+                channel_id,
+                counterparty_node_id,
+                amount_satoshis,
+                claimable_height,
+                payment_hash,
+                outbound_payment,
+                ..
+            } => ChannelBalance::MaybeTimeoutClaimableHtlc {
+                channel_id: channel_id.to_string(),
+                counterparty_node_id: *counterparty_node_id,
+                amount_sats: *amount_satoshis,
+                claimable_height: *claimable_height,
+                payment_hash: payment_hash.0.to_lower_hex_string(),
+                outbound_payment: *outbound_payment,
+            },
+            LightningBalance::MaybePreimageClaimableHTLC {
+                channel_id,
+                counterparty_node_id,
+                amount_satoshis,
+                expiry_height,
+                payment_hash,
+                ..
+            } => ChannelBalance::MaybePreimageClaimableHtlc {
+                channel_id: channel_id.to_string(),
+                counterparty_node_id: *counterparty_node_id,
+                amount_sats: *amount_satoshis,
+                expiry_height: *expiry_height,
+                payment_hash: payment_hash.0.to_lower_hex_string(),
+            },
+            LightningBalance::CounterpartyRevokedOutputClaimable {
+                channel_id,
+                counterparty_node_id,
+                amount_satoshis,
+                ..
+            } => ChannelBalance::CounterpartyRevokedOutputClaimable {
+                channel_id: channel_id.to_string(),
+                counterparty_node_id: *counterparty_node_id,
+                amount_sats: *amount_satoshis,
+            },
+        }
+    }
+}
+
+/// Funds of a closed channel handed to the output sweeper, see `ldk_node::PendingSweepBalance`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PendingSweep {
+    /// The sweeping transaction wasn't broadcast yet
+    PendingBroadcast {
+        channel_id: Option<String>,
+        amount_sats: u64,
+    },
+    /// The sweeping transaction is broadcast but not confirmed
+    BroadcastAwaitingConfirmation {
+        channel_id: Option<String>,
+        amount_sats: u64,
+        latest_broadcast_height: u32,
+        latest_spending_txid: String,
+    },
+    /// The sweeping transaction is confirmed but not deep enough yet
+    AwaitingThresholdConfirmations {
+        channel_id: Option<String>,
+        amount_sats: u64,
+        latest_spending_txid: String,
+        confirmation_height: u32,
+    },
+}
+
+impl PendingSweep {
+    pub fn channel_id(&self) -> Option<&str> {
+        match self {
+            PendingSweep::PendingBroadcast { channel_id, .. }
+            | PendingSweep::BroadcastAwaitingConfirmation { channel_id, .. }
+            | PendingSweep::AwaitingThresholdConfirmations { channel_id, .. } => {
+                channel_id.as_deref()
+            }
+        }
+    }
+
+    pub fn amount_sats(&self) -> u64 {
+        match self {
+            PendingSweep::PendingBroadcast { amount_sats, .. }
+            | PendingSweep::BroadcastAwaitingConfirmation { amount_sats, .. }
+            | PendingSweep::AwaitingThresholdConfirmations { amount_sats, .. } => *amount_sats,
+        }
+    }
+}
+
+impl From<&PendingSweepBalance> for PendingSweep {
+    fn from(balance: &PendingSweepBalance) -> Self {
+        match balance {
+            PendingSweepBalance::PendingBroadcast {
+                channel_id,
+                amount_satoshis,
+                ..
+            } => PendingSweep::PendingBroadcast {
+                channel_id: channel_id.map(|id| id.to_string()),
+                amount_sats: *amount_satoshis,
+            },
+            PendingSweepBalance::BroadcastAwaitingConfirmation {
+                channel_id,
+                latest_broadcast_height,
+                latest_spending_txid,
+                amount_satoshis,
+                ..
+            } => PendingSweep::BroadcastAwaitingConfirmation {
+                channel_id: channel_id.map(|id| id.to_string()),
+                amount_sats: *amount_satoshis,
+                latest_broadcast_height: *latest_broadcast_height,
+                latest_spending_txid: latest_spending_txid.to_string(),
+            },
+            PendingSweepBalance::AwaitingThresholdConfirmations {
+                channel_id,
+                latest_spending_txid,
+                confirmation_height,
+                amount_satoshis,
+                ..
+            } => PendingSweep::AwaitingThresholdConfirmations {
+                channel_id: channel_id.map(|id| id.to_string()),
+                amount_sats: *amount_satoshis,
+                latest_spending_txid: latest_spending_txid.to_string(),
+                confirmation_height: *confirmation_height,
+            },
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetPaymentResponse {
-    pub status: String,
+    #[serde(flatten)]
+    pub payment: Payment,
+    /// Reason reported by the `PaymentFailed` event, if the payment failed
+    pub failure_reason: Option<String>,
+    /// Custom TLV records received with a spontaneous payment
+    pub custom_tlvs: Vec<CustomTlv>,
+}
+
+/// Custom TLV record attached to a keysend payment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomTlv {
+    pub type_num: u64,
+    /// Hex encoded value
+    pub value: String,
+}
+
+impl From<&CustomTlvRecord> for CustomTlv {
+    fn from(record: &CustomTlvRecord) -> Self {
+        Self {
+            type_num: record.type_num,
+            value: record.value.to_lower_hex_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeysendRequest {
+    pub pubkey: PublicKey,
+    pub amount_msat: u64,
+    #[serde(default)]
+    pub custom_tlvs: Vec<CustomTlv>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeysendResponse {
+    pub payment_id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentDirection {
+    Inbound,
+    Outbound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    Pending,
+    Succeeded,
+    Failed,
+}
+
+/// How the payment was made, BOLT12 offers and refunds as well as JIT invoices are folded into
+/// their protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentKind {
+    Bolt11,
+    Bolt12,
+    Spontaneous,
+    Onchain,
+}
+
+/// Payment as tracked by the node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Payment {
+    pub payment_id: String,
+    pub direction: PaymentDirection,
+    pub status: PaymentStatus,
+    pub kind: PaymentKind,
+    pub amount_msat: Option<u64>,
+    pub fee_paid_msat: Option<u64>,
+    pub payment_hash: Option<String>,
+    pub payment_preimage: Option<String>,
+    /// Onchain transaction id, for onchain payments
+    pub txid: Option<String>,
+    /// Unix timestamp of the last status change
+    pub latest_update_timestamp: u64,
+}
+
+impl From<&PaymentDetails> for Payment {
+    fn from(details: &PaymentDetails) -> Self {
+        let (kind, hash, preimage, txid) = match &details.kind {
+            ldk_node::payment::PaymentKind::Onchain { txid, .. } => {
+                (PaymentKind::Onchain, None, None, Some(txid.to_string()))
+            }
+            ldk_node::payment::PaymentKind::Bolt11 { hash, preimage, .. }
+            | ldk_node::payment::PaymentKind::Bolt11Jit { hash, preimage, .. } => {
+                (PaymentKind::Bolt11, Some(*hash), *preimage, None)
+            }
+            ldk_node::payment::PaymentKind::Bolt12Offer { hash, preimage, .. }
+            | ldk_node::payment::PaymentKind::Bolt12Refund { hash, preimage, .. } => {
+                (PaymentKind::Bolt12, *hash, *preimage, None)
+            }
+            ldk_node::payment::PaymentKind::Spontaneous { hash, preimage, .. } => {
+                (PaymentKind::Spontaneous, Some(*hash), *preimage, None)
+            }
+        };
+
+        Self {
+            payment_id: details.id.0.to_lower_hex_string(),
+            direction: match details.direction {
+                ldk_node::payment::PaymentDirection::Inbound => PaymentDirection::Inbound,
+                ldk_node::payment::PaymentDirection::Outbound => PaymentDirection::Outbound,
+            },
+            status: match details.status {
+                ldk_node::payment::PaymentStatus::Pending => PaymentStatus::Pending,
+                ldk_node::payment::PaymentStatus::Succeeded => PaymentStatus::Succeeded,
+                ldk_node::payment::PaymentStatus::Failed => PaymentStatus::Failed,
+            },
+            kind,
+            amount_msat: details.amount_msat,
+            fee_paid_msat: details.fee_paid_msat,
+            payment_hash: hash.map(|hash| hash.0.to_lower_hex_string()),
+            payment_preimage: preimage.map(|preimage| preimage.0.to_lower_hex_string()),
+            txid,
+            latest_update_timestamp: details.latest_update_timestamp,
+        }
+    }
+}
+
+/// Filters and pagination of `GET /payments`, all filters are optional
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListPaymentsQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<PaymentDirection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<PaymentStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<PaymentKind>,
+    /// Only payments last updated at or after this unix timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<u64>,
+    /// Only payments last updated at or before this unix timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<u64>,
+    /// Number of matching payments to skip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    /// Maximum number of payments to return
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl ListPaymentsQuery {
+    pub fn matches(&self, payment: &Payment) -> bool {
+        self.direction.is_none_or(|d| d == payment.direction)
+            && self.status.is_none_or(|s| s == payment.status)
+            && self.kind.is_none_or(|k| k == payment.kind)
+            && self
+                .since
+                .is_none_or(|since| payment.latest_update_timestamp >= since)
+            && self
+                .until
+                .is_none_or(|until| payment.latest_update_timestamp <= until)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListPaymentsResponse {
+    pub payments: Vec<Payment>,
+    /// Number of payments matching the filters, before pagination
+    pub total: usize,
+}
+
+/// Node event as streamed by `GET /events`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeEvent {
+    PaymentReceived {
+        payment_id: Option<String>,
+        payment_hash: String,
+        amount_msat: u64,
+        #[serde(default)]
+        custom_tlvs: Vec<CustomTlv>,
+    },
+    /// A payment to a hold invoice arrived and waits to be claimed or failed
+    PaymentClaimable {
+        payment_id: String,
+        payment_hash: String,
+        claimable_amount_msat: u64,
+        claim_deadline: Option<u32>,
+    },
+    PaymentSuccessful {
+        payment_id: Option<String>,
+        payment_hash: String,
+        payment_preimage: Option<String>,
+        fee_paid_msat: Option<u64>,
+    },
+    PaymentFailed {
+        payment_id: Option<String>,
+        payment_hash: Option<String>,
+        reason: Option<String>,
+    },
+    PaymentForwarded {
+        prev_channel_id: String,
+        next_channel_id: String,
+        total_fee_earned_msat: Option<u64>,
+        outbound_amount_forwarded_msat: Option<u64>,
+    },
+    ChannelPending {
+        channel_id: String,
+        user_channel_id: u128,
+        counterparty_node_id: PublicKey,
+        funding_txo: String,
+    },
+    ChannelReady {
+        channel_id: String,
+        user_channel_id: u128,
+        counterparty_node_id: Option<PublicKey>,
+    },
+    ChannelClosed {
+        channel_id: String,
+        user_channel_id: u128,
+        counterparty_node_id: Option<PublicKey>,
+        reason: Option<String>,
+    },
+}
+
+impl NodeEvent {
+    /// Converts the events we stream, returns `None` for the others
+    pub fn from_event(event: &Event) -> Option<Self> {
+        let event = match event {
+            Event::PaymentReceived {
+                payment_id,
+                payment_hash,
+                amount_msat,
+                custom_records,
+                ..
+            } => NodeEvent::PaymentReceived {
+                payment_id: payment_id.map(|id| id.0.to_lower_hex_string()),
+                payment_hash: payment_hash.0.to_lower_hex_string(),
+                amount_msat: *amount_msat,
+                custom_tlvs: custom_records.iter().map(CustomTlv::from).collect(),
+            },
+            Event::PaymentClaimable {
+                payment_id,
+                payment_hash,
+                claimable_amount_msat,
+                claim_deadline,
+                ..
+            } => NodeEvent::PaymentClaimable {
+                payment_id: payment_id.0.to_lower_hex_string(),
+                payment_hash: payment_hash.0.to_lower_hex_string(),
+                claimable_amount_msat: *claimable_amount_msat,
+                claim_deadline: *claim_deadline,
+            },
+            Event::PaymentSuccessful {
+                payment_id,
+                payment_hash,
+                payment_preimage,
+                fee_paid_msat,
+                ..
+            } => NodeEvent::PaymentSuccessful {
+                payment_id: payment_id.map(|id| id.0.to_lower_hex_string()),
+                payment_hash: payment_hash.0.to_lower_hex_string(),
+                payment_preimage: payment_preimage.map(|preimage| preimage.0.to_lower_hex_string()),
+                fee_paid_msat: *fee_paid_msat,
+            },
+            Event::PaymentFailed {
+                payment_id,
+                payment_hash,
+                reason,
+                ..
+            } => NodeEvent::PaymentFailed {
+                payment_id: payment_id.map(|id| id.0.to_lower_hex_string()),
+                payment_hash: payment_hash.map(|hash| hash.0.to_lower_hex_string()),
+                reason: reason.as_ref().map(|reason| format!("{:?}", reason)),
+            },
+            Event::PaymentForwarded {
+                prev_channel_id,
+                next_channel_id,
+                total_fee_earned_msat,
+                outbound_amount_forwarded_msat,
+                ..
+            } => NodeEvent::PaymentForwarded {
+                prev_channel_id: prev_channel_id.to_string(),
+                next_channel_id: next_channel_id.to_string(),
+                total_fee_earned_msat: *total_fee_earned_msat,
+                outbound_amount_forwarded_msat: *outbound_amount_forwarded_msat,
+            },
+            Event::ChannelPending {
+                channel_id,
+                user_channel_id,
+                counterparty_node_id,
+                funding_txo,
+                ..
+            } => NodeEvent::ChannelPending {
+                channel_id: channel_id.to_string(),
+                user_channel_id: user_channel_id.0,
+                counterparty_node_id: *counterparty_node_id,
+                funding_txo: funding_txo.to_string(),
+            },
+            Event::ChannelReady {
+                channel_id,
+                user_channel_id,
+                counterparty_node_id,
+                ..
+            } => NodeEvent::ChannelReady {
+                channel_id: channel_id.to_string(),
+                user_channel_id: user_channel_id.0,
+                counterparty_node_id: *counterparty_node_id,
+            },
+            Event::ChannelClosed {
+                channel_id,
+                user_channel_id,
+                counterparty_node_id,
+                reason,
+                ..
+            } => NodeEvent::ChannelClosed {
+                channel_id: channel_id.to_string(),
+                user_channel_id: user_channel_id.0,
+                counterparty_node_id: *counterparty_node_id,
+                reason: reason.as_ref().map(|reason| reason.to_string()),
+            },
+            _ => return None,
+        };
+        Some(event)
+    }
+}
+
+/// Body returned by the http api whenever a request fails
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: String,
+    pub message: String,
+    pub details: Option<String>,
 }
 
 #[derive(Debug)]
@@ -257,6 +1511,24 @@ pub struct Conf<'a> {
     /// RGS Url
     pub rgs_url: Option<String>,
 
+    /// LSPS2 service parameters, passed to the daemon on the command line
+    pub lsps2: Lsps2Config,
+
+    /// Enables the LSPS1 service with the given parameters
+    pub lsps1: Option<Lsps1Config>,
+
+    /// Alias announced to the network, needed to open announced channels
+    pub node_alias: Option<String>,
+
+    /// `host:port` addresses announced to the network, the listening address when empty
+    pub announcement_addresses: Vec<String>,
+
+    /// Peers whose channels to the node are accepted as zero-conf
+    pub trusted_peers_0conf: Vec<PublicKey>,
+
+    /// Makes the payer node the daemon spawns on regtest accept its zero-conf channels
+    pub payer_trusts_lsp_0conf: bool,
+
     /// Try to spawn the process `attempt` time
     ///
     /// The OS is giving available ports to use, however, they aren't booked, so it could rarely
@@ -275,6 +1547,12 @@ impl Default for Conf<'_> {
             attempts: 3,
             esplora_url: None,
             rgs_url: None,
+            lsps2: Lsps2Config::default(),
+            lsps1: None,
+            node_alias: None,
+            announcement_addresses: Vec::new(),
+            trusted_peers_0conf: Vec::new(),
+            payer_trusts_lsp_0conf: false,
         }
     }
 }
@@ -343,6 +1621,31 @@ impl LspsD {
             args.push(rgs_url.to_string());
         }
 
+        args.extend(lsps2_args(&conf.lsps2));
+
+        if let Some(lsps1) = &conf.lsps1 {
+            args.extend(lsps1_args(lsps1));
+        }
+
+        if let Some(node_alias) = &conf.node_alias {
+            args.push("--node-alias".to_string());
+            args.push(node_alias.to_string());
+        }
+
+        for address in &conf.announcement_addresses {
+            args.push("--announcement-address".to_string());
+            args.push(address.to_string());
+        }
+
+        for node_id in &conf.trusted_peers_0conf {
+            args.push("--trusted-peers-0conf".to_string());
+            args.push(node_id.to_string());
+        }
+
+        if conf.payer_trusts_lsp_0conf {
+            args.push("--payer-trusts-lsp-0conf".to_string());
+        }
+
         debug!("launching {:?} with args: {:?}", exe.as_ref(), args);
 
         let mut process = Command::new(exe.as_ref())
@@ -406,6 +1709,16 @@ impl LspsD {
         self.work_dir.path()
     }
 
+    /// Mines `count` blocks on the node's bitcoind and waits for the node to sync them
+    pub fn mine_blocks(&self, count: u64) -> Result<MineBlocksResponse, error::ClientError> {
+        self.client.mine_blocks(count, None)
+    }
+
+    /// Best block known to the node
+    pub fn chain_tip(&self) -> Result<ChainTip, error::ClientError> {
+        self.client.chain_tip()
+    }
+
     /// Stop the node, waiting correct process termination
     pub fn stop(&mut self) -> anyhow::Result<ExitStatus> {
         // TODO: impl stop
@@ -433,6 +1746,56 @@ impl Drop for LspsD {
     }
 }
 
+/// Command line arguments passing the given LSPS2 service parameters to the daemon
+fn lsps2_args(lsps2: &Lsps2Config) -> Vec<String> {
+    let mut args = vec![
+        "--lsps2-advertise-service".to_string(),
+        lsps2.advertise_service.to_string(),
+        "--lsps2-channel-opening-fee-ppm".to_string(),
+        lsps2.channel_opening_fee_ppm.to_string(),
+        "--lsps2-channel-over-provisioning-ppm".to_string(),
+        lsps2.channel_over_provisioning_ppm.to_string(),
+        "--lsps2-min-channel-opening-fee-msat".to_string(),
+        lsps2.min_channel_opening_fee_msat.to_string(),
+        "--lsps2-min-channel-lifetime".to_string(),
+        lsps2.min_channel_lifetime.to_string(),
+        "--lsps2-max-client-to-self-delay".to_string(),
+        lsps2.max_client_to_self_delay.to_string(),
+        "--lsps2-min-payment-size-msat".to_string(),
+        lsps2.min_payment_size_msat.to_string(),
+        "--lsps2-max-payment-size-msat".to_string(),
+        lsps2.max_payment_size_msat.to_string(),
+    ];
+    if let Some(token) = &lsps2.require_token {
+        args.push("--lsps2-require-token".to_string());
+        args.push(token.to_string());
+    }
+    args
+}
+
+/// Command line arguments enabling the LSPS1 service with the given parameters
+fn lsps1_args(lsps1: &Lsps1Config) -> Vec<String> {
+    vec![
+        "--lsps1".to_string(),
+        "--lsps1-min-initial-lsp-balance-sat".to_string(),
+        lsps1.min_initial_lsp_balance_sat.to_string(),
+        "--lsps1-max-initial-lsp-balance-sat".to_string(),
+        lsps1.max_initial_lsp_balance_sat.to_string(),
+        "--lsps1-min-initial-client-balance-sat".to_string(),
+        lsps1.min_initial_client_balance_sat.to_string(),
+        "--lsps1-max-initial-client-balance-sat".to_string(),
+        lsps1.max_initial_client_balance_sat.to_string(),
+        "--lsps1-max-channel-expiry-blocks".to_string(),
+        lsps1.max_channel_expiry_blocks.to_string(),
+        "--lsps1-fee-base-sat".to_string(),
+        lsps1.fee_base_sat.to_string(),
+        "--lsps1-fee-ppm".to_string(),
+        lsps1.fee_ppm.to_string(),
+        "--lsps1-order-expiry-secs".to_string(),
+        lsps1.order_expiry_secs.to_string(),
+    ]
+}
+
 /// Returns a non-used local port if available.
 ///
 /// Note there is a race condition during the time the method check availability and the caller
@@ -479,3 +1842,82 @@ pub fn exe_path() -> anyhow::Result<String> {
 pub fn validate_args(args: Vec<&str>) -> anyhow::Result<Vec<&str>> {
     Ok(args)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payment(direction: PaymentDirection, status: PaymentStatus, kind: PaymentKind) -> Payment {
+        Payment {
+            payment_id: "00".repeat(32),
+            direction,
+            status,
+            kind,
+            amount_msat: Some(1000),
+            fee_paid_msat: None,
+            payment_hash: None,
+            payment_preimage: None,
+            txid: None,
+            latest_update_timestamp: 1_000,
+        }
+    }
+
+    #[test]
+    fn list_payments_query_matches_filter_combinations() {
+        use PaymentDirection::*;
+        use PaymentKind::*;
+        use PaymentStatus::*;
+
+        let received = payment(Inbound, Succeeded, Bolt11);
+        let sent = payment(Outbound, Pending, Onchain);
+
+        let all = ListPaymentsQuery::default();
+        assert!(all.matches(&received) && all.matches(&sent));
+
+        let inbound = ListPaymentsQuery {
+            direction: Some(Inbound),
+            ..Default::default()
+        };
+        assert!(inbound.matches(&received) && !inbound.matches(&sent));
+
+        // every filter that is set must match
+        let inbound_bolt11 = ListPaymentsQuery {
+            kind: Some(Bolt11),
+            status: Some(Succeeded),
+            ..inbound.clone()
+        };
+        assert!(inbound_bolt11.matches(&received));
+        let inbound_pending = ListPaymentsQuery {
+            status: Some(Pending),
+            ..inbound.clone()
+        };
+        assert!(!inbound_pending.matches(&received) && !inbound_pending.matches(&sent));
+        let outbound_bolt12 = ListPaymentsQuery {
+            direction: Some(Outbound),
+            kind: Some(Bolt12),
+            ..Default::default()
+        };
+        assert!(!outbound_bolt12.matches(&sent));
+    }
+
+    #[test]
+    fn list_payments_query_time_range_is_inclusive() {
+        let payment = payment(
+            PaymentDirection::Inbound,
+            PaymentStatus::Succeeded,
+            PaymentKind::Spontaneous,
+        );
+        let range = |since, until| ListPaymentsQuery {
+            since,
+            until,
+            ..Default::default()
+        };
+
+        assert!(range(Some(1_000), Some(1_000)).matches(&payment));
+        assert!(range(Some(999), None).matches(&payment));
+        assert!(range(None, Some(1_001)).matches(&payment));
+        assert!(!range(Some(1_001), None).matches(&payment));
+        assert!(!range(None, Some(999)).matches(&payment));
+        assert!(!range(Some(0), Some(999)).matches(&payment));
+    }
+}
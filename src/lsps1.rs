@@ -0,0 +1,287 @@
+//! LSPS1 channel requests.
+//!
+//! ldk-node only ships an LSPS1 client, so orders are placed through the http api instead of
+//! over lightning. An LSPS1 client talking to the node over lightning, such as ldk-node's, gets
+//! no answer. The order and payment states follow the LSPS1 spec: the order is paid with a
+//! bolt11 invoice and the node opens the requested channel to the client once it is paid. If the
+//! channel can't be opened the payment is refunded to the order's `refund_onchain_address`, if
+//! any.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::Json;
+use ldk_node::lightning::ln::msgs::SocketAddress;
+use ldk_node::lightning_invoice::{Bolt11InvoiceDescription, Description};
+use lspsd::error::{ApiError, ApiResult};
+use lspsd::{
+    Lsps1Channel, Lsps1Config, Lsps1CreateOrderRequest, Lsps1ListOrdersResponse, Lsps1Order,
+    Lsps1OrderState, Lsps1Payment, Lsps1PaymentState, NodeEvent,
+};
+
+use crate::{open_channel_and_mine, random_id, sats_to_msat, unix_now, AppState};
+
+fn lsps1_config(state: &AppState) -> Result<&Lsps1Config, ApiError> {
+    state
+        .lsps1
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("lsps1_disabled", "the lsps1 service is not enabled"))
+}
+
+fn check_range(field: &str, value: u64, min: u64, max: u64) -> Result<(), ApiError> {
+    if value < min || value > max {
+        return Err(ApiError::bad_request(
+            "option_mismatch",
+            format!("{} must be between {} and {}", field, min, max),
+        )
+        .with_details(value));
+    }
+    Ok(())
+}
+
+pub(crate) async fn get_info(State(state): State<AppState>) -> ApiResult<Lsps1Config> {
+    Ok(Json(lsps1_config(&state)?.clone()))
+}
+
+pub(crate) async fn create_order(
+    State(state): State<AppState>,
+    Json(req): Json<Lsps1CreateOrderRequest>,
+) -> ApiResult<Lsps1Order> {
+    let config = lsps1_config(&state)?;
+    check_range(
+        "lsp_balance_sat",
+        req.lsp_balance_sat,
+        config.min_initial_lsp_balance_sat,
+        config.max_initial_lsp_balance_sat,
+    )?;
+    check_range(
+        "client_balance_sat",
+        req.client_balance_sat,
+        config.min_initial_client_balance_sat,
+        config.max_initial_client_balance_sat,
+    )?;
+    check_range(
+        "channel_expiry_blocks",
+        req.channel_expiry_blocks as u64,
+        1,
+        config.max_channel_expiry_blocks as u64,
+    )?;
+
+    let client_address = match &req.client_address {
+        Some(address) => Some(SocketAddress::from_str(address).map_err(|e| {
+            ApiError::bad_request("invalid_socket_address", "could not parse client_address")
+                .with_details(e)
+        })?),
+        None => None,
+    };
+    if let Some(address) = &req.refund_onchain_address {
+        ldk_node::bitcoin::Address::from_str(address).map_err(|e| {
+            ApiError::bad_request("invalid_address", "could not parse refund_onchain_address")
+                .with_details(e)
+        })?;
+    }
+
+    let order_id = random_id();
+    let fee_total_sat = config.fee_sat(req.lsp_balance_sat, req.client_balance_sat);
+    let order_total_sat = fee_total_sat + req.client_balance_sat;
+
+    let description = Description::new(format!("LSPS1 order {}", order_id))
+        .map_err(|e| ApiError::internal("invalid_description", e.to_string()))?;
+    let invoice = state.node.bolt11_payment().receive(
+        sats_to_msat("order_total_sat", order_total_sat)?,
+        &Bolt11InvoiceDescription::Direct(description),
+        config.order_expiry_secs,
+    )?;
+
+    let created_at = unix_now();
+    let expires_at = created_at + config.order_expiry_secs as u64;
+    let order = Lsps1Order {
+        order_id: order_id.clone(),
+        client_node_id: req.client_node_id,
+        lsp_balance_sat: req.lsp_balance_sat,
+        client_balance_sat: req.client_balance_sat,
+        channel_expiry_blocks: req.channel_expiry_blocks,
+        created_at,
+        expires_at,
+        order_state: Lsps1OrderState::Created,
+        payment: Lsps1Payment {
+            state: Lsps1PaymentState::ExpectPayment,
+            fee_total_sat,
+            order_total_sat,
+            bolt11_invoice: invoice.to_string(),
+            payment_hash: invoice.payment_hash().to_string(),
+        },
+        channel: None,
+        failure_reason: None,
+        refund_onchain_address: req.refund_onchain_address,
+    };
+
+    state
+        .lsps1_orders
+        .lock()
+        .unwrap()
+        .insert(order_id.clone(), order.clone());
+
+    // inbound bolt11 payments are identified by their payment hash
+    let payment_id = order.payment.payment_hash.clone();
+    tokio::spawn(watch_order(
+        state,
+        order_id,
+        payment_id,
+        expires_at,
+        client_address,
+    ));
+
+    Ok(Json(order))
+}
+
+pub(crate) async fn list_orders(
+    State(state): State<AppState>,
+) -> ApiResult<Lsps1ListOrdersResponse> {
+    lsps1_config(&state)?;
+    let mut orders = state
+        .lsps1_orders
+        .lock()
+        .unwrap()
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    orders.sort_by_key(|order| order.created_at);
+
+    Ok(Json(Lsps1ListOrdersResponse { orders }))
+}
+
+pub(crate) async fn get_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+) -> ApiResult<Lsps1Order> {
+    lsps1_config(&state)?;
+    let order = state
+        .lsps1_orders
+        .lock()
+        .unwrap()
+        .get(&order_id)
+        .cloned()
+        .ok_or_else(|| {
+            ApiError::not_found("order_not_found", format!("no order with id {}", order_id))
+        })?;
+
+    Ok(Json(order))
+}
+
+fn update_order(
+    state: &AppState,
+    order_id: &str,
+    f: impl FnOnce(&mut Lsps1Order),
+) -> Option<Lsps1Order> {
+    let mut orders = state.lsps1_orders.lock().unwrap();
+    let order = orders.get_mut(order_id)?;
+    f(order);
+    Some(order.clone())
+}
+
+fn fail_order(state: &AppState, order_id: &str, reason: String) {
+    println!("lsps1 order {} failed: {}", order_id, reason);
+    update_order(state, order_id, |order| {
+        order.order_state = Lsps1OrderState::Failed;
+        order.failure_reason = Some(reason);
+    });
+}
+
+/// Fails an order that was already paid and refunds the payment onchain if the client gave an
+/// address. The payment is only marked refunded once the refund was sent, it stays paid otherwise
+fn refund_order(state: &AppState, order: &Lsps1Order, reason: String) {
+    let Some(address) = &order.refund_onchain_address else {
+        let reason = format!("{}, no refund_onchain_address to refund to", reason);
+        fail_order(state, &order.order_id, reason);
+        return;
+    };
+
+    let address = ldk_node::bitcoin::Address::from_str(address)
+        .expect("refund address is checked when creating the order")
+        .assume_checked();
+    let onchain_payment = state.node.onchain_payment();
+    match onchain_payment.send_to_address(&address, order.payment.order_total_sat, None) {
+        Ok(txid) => {
+            let reason = format!("{}, refunded in {}", reason, txid);
+            fail_order(state, &order.order_id, reason);
+            update_order(state, &order.order_id, |order| {
+                order.payment.state = Lsps1PaymentState::Refunded;
+            });
+        }
+        Err(e) => {
+            let reason = format!("{}, refund failed: {}", reason, e);
+            fail_order(state, &order.order_id, reason);
+        }
+    }
+}
+
+/// Waits for the order to be paid, then opens the channel to the client
+async fn watch_order(
+    state: AppState,
+    order_id: String,
+    payment_id: String,
+    expires_at: u64,
+    client_address: Option<SocketAddress>,
+) {
+    let expires_in = Duration::from_secs(expires_at.saturating_sub(unix_now()));
+    let payment =
+        tokio::time::timeout(expires_in, state.tracker.wait_for_payment(&payment_id)).await;
+    match payment {
+        Ok(NodeEvent::PaymentReceived { .. }) => {}
+        Ok(_) => {
+            fail_order(&state, &order_id, "payment failed".to_string());
+            return;
+        }
+        Err(_) => {
+            fail_order(
+                &state,
+                &order_id,
+                "order expired before being paid".to_string(),
+            );
+            return;
+        }
+    }
+
+    let Some(order) = update_order(&state, &order_id, |order| {
+        order.payment.state = Lsps1PaymentState::Paid;
+    }) else {
+        return;
+    };
+
+    let client_address = client_address.or_else(|| {
+        state
+            .node
+            .list_peers()
+            .into_iter()
+            .find(|peer| peer.node_id == order.client_node_id)
+            .map(|peer| peer.address)
+    });
+    let Some(client_address) = client_address else {
+        refund_order(&state, &order, "client is not connected".to_string());
+        return;
+    };
+
+    let res = open_channel_and_mine(
+        &state,
+        order.client_node_id,
+        client_address,
+        order.lsp_balance_sat + order.client_balance_sat,
+        order.client_balance_sat * 1000,
+    )
+    .await;
+
+    match res {
+        Ok(user_channel_id) => {
+            update_order(&state, &order_id, |order| {
+                order.order_state = Lsps1OrderState::Completed;
+                order.channel = Some(Lsps1Channel {
+                    funded_at: unix_now(),
+                    user_channel_id: user_channel_id.0,
+                });
+            });
+        }
+        Err(e) => refund_order(&state, &order, e.to_string()),
+    }
+}
@@ -0,0 +1,287 @@
+//! LSPS5 webhook notifications and a local webhook sink.
+//!
+//! ldk-node has no LSPS5 service, so clients register their webhooks through the http api.
+//!
+//! ldk-node intercepts the HTLCs of LSPS2 JIT channels internally and exposes no hook for it, so
+//! the notification can't go out when the HTLC is intercepted. Registered clients instead get a
+//! `lsps5.payment_incoming` notification once the node has opened a JIT channel to them, which
+//! means they had to be online for the open. This is not the wake-up notification LSPS5 is meant
+//! for. Channels opened through the http api or for LSPS1 orders don't trigger notifications.
+//!
+//! Notifications can be pointed at the built-in `/webhook-sink/{tag}` endpoint so tests can
+//! assert they went out.
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use ldk_node::bitcoin::secp256k1::PublicKey;
+use ldk_node::Node;
+use lspsd::error::{ApiError, ApiResult};
+use lspsd::{
+    ListWebhookDeliveriesResponse, Lsps5ListWebhooksResponse, Lsps5SetWebhookRequest, Lsps5Webhook,
+    WebhookDelivery,
+};
+use serde_json::{json, Value};
+
+use crate::{unix_now, AppState};
+
+/// How often the node is checked for newly opened JIT channels
+const CHANNEL_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Maximum number of webhooks a single client may register
+const MAX_WEBHOOKS_PER_CLIENT: usize = 5;
+
+const MAX_APP_NAME_LENGTH: usize = 64;
+const MAX_WEBHOOK_URL_LENGTH: usize = 1024;
+
+const WEBHOOK_REGISTERED: &str = "lsps5.webhook_registered";
+const PAYMENT_INCOMING: &str = "lsps5.payment_incoming";
+
+/// Formats a unix timestamp as `YYYY-MM-DDThh:mm:ss.000Z`, the format LSPS5 uses for timestamps
+fn iso8601(unix_secs: u64) -> String {
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = (unix_secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let secs_of_day = unix_secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.000Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Posts a signed LSPS5 notification to the given webhook
+fn send_notification(node: &Node, webhook: &str, method: &str) -> Result<(), minreq::Error> {
+    let body = json!({"jsonrpc": "2.0", "method": method, "params": {}}).to_string();
+    let timestamp = iso8601(unix_now());
+    let message = format!(
+        "LSPS5: DO NOT SIGN THIS MESSAGE MANUALLY: LSP: At {} I notify {}",
+        timestamp, body
+    );
+    let signature = node.sign_message(message.as_bytes());
+
+    minreq::post(webhook)
+        .with_header("Content-Type", "application/json")
+        .with_header("x-lsps5-timestamp", timestamp)
+        .with_header("x-lsps5-signature", signature)
+        .with_body(body)
+        .with_timeout(10)
+        .send()?;
+    Ok(())
+}
+
+/// Sends the notification to the given webhooks in the background
+fn notify(state: &AppState, webhooks: Vec<Lsps5Webhook>, method: &'static str) {
+    for webhook in webhooks {
+        let node = state.node.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = send_notification(&node, &webhook.webhook, method) {
+                println!("failed to deliver {} to {}: {}", method, webhook.webhook, e);
+            }
+        });
+    }
+}
+
+fn parse_node_id(node_id: &str) -> Result<PublicKey, ApiError> {
+    PublicKey::from_str(node_id).map_err(|e| {
+        ApiError::bad_request("invalid_node_id", "could not parse node id").with_details(e)
+    })
+}
+
+pub(crate) async fn set_webhook(
+    State(state): State<AppState>,
+    Json(req): Json<Lsps5SetWebhookRequest>,
+) -> ApiResult<Lsps5Webhook> {
+    if req.app_name.len() > MAX_APP_NAME_LENGTH {
+        return Err(ApiError::bad_request(
+            "app_name_too_long",
+            format!("app_name must be at most {} bytes", MAX_APP_NAME_LENGTH),
+        ));
+    }
+    if req.webhook.len() > MAX_WEBHOOK_URL_LENGTH
+        || !(req.webhook.starts_with("http://") || req.webhook.starts_with("https://"))
+    {
+        return Err(ApiError::bad_request(
+            "unsupported_protocol",
+            "webhook must be an http(s) url",
+        )
+        .with_details(&req.webhook));
+    }
+
+    let webhook = Lsps5Webhook {
+        client_node_id: req.client_node_id,
+        app_name: req.app_name,
+        webhook: req.webhook,
+        registered_at: unix_now(),
+    };
+
+    let changed = {
+        let mut webhooks = state.lsps5_webhooks.lock().unwrap();
+        let existing = webhooks.iter().position(|w| {
+            w.client_node_id == webhook.client_node_id && w.app_name == webhook.app_name
+        });
+        match existing {
+            Some(i) => {
+                let changed = webhooks[i].webhook != webhook.webhook;
+                webhooks[i] = webhook.clone();
+                changed
+            }
+            None => {
+                let registered = webhooks
+                    .iter()
+                    .filter(|w| w.client_node_id == webhook.client_node_id)
+                    .count();
+                if registered >= MAX_WEBHOOKS_PER_CLIENT {
+                    return Err(ApiError::bad_request(
+                        "too_many_webhooks",
+                        format!(
+                            "a client can register at most {} webhooks",
+                            MAX_WEBHOOKS_PER_CLIENT
+                        ),
+                    ));
+                }
+                webhooks.push(webhook.clone());
+                true
+            }
+        }
+    };
+
+    if changed {
+        notify(&state, vec![webhook.clone()], WEBHOOK_REGISTERED);
+    }
+
+    Ok(Json(webhook))
+}
+
+pub(crate) async fn list_webhooks(
+    State(state): State<AppState>,
+) -> ApiResult<Lsps5ListWebhooksResponse> {
+    let webhooks = state.lsps5_webhooks.lock().unwrap().clone();
+    Ok(Json(Lsps5ListWebhooksResponse { webhooks }))
+}
+
+pub(crate) async fn remove_webhook(
+    State(state): State<AppState>,
+    Path((client_node_id, app_name)): Path<(String, String)>,
+) -> ApiResult<Lsps5Webhook> {
+    let client_node_id = parse_node_id(&client_node_id)?;
+    let mut webhooks = state.lsps5_webhooks.lock().unwrap();
+    let i = webhooks
+        .iter()
+        .position(|w| w.client_node_id == client_node_id && w.app_name == app_name)
+        .ok_or_else(|| {
+            ApiError::not_found(
+                "app_name_not_found",
+                format!("no webhook registered for {}", app_name),
+            )
+        })?;
+
+    Ok(Json(webhooks.remove(i)))
+}
+
+/// Notifies registered clients about new JIT channels opened to them
+pub(crate) async fn watch_channels(state: AppState) {
+    let mut seen = state
+        .node
+        .list_channels()
+        .into_iter()
+        .map(|channel| channel.user_channel_id.0)
+        .collect::<HashSet<_>>();
+
+    loop {
+        tokio::time::sleep(CHANNEL_POLL_INTERVAL).await;
+
+        for channel in state.node.list_channels() {
+            // wait for the funding transaction, by then channels opened through the api are
+            // recorded as such
+            if !channel.is_outbound
+                || channel.funding_txo.is_none()
+                || !seen.insert(channel.user_channel_id.0)
+            {
+                continue;
+            }
+            if state
+                .api_channels
+                .lock()
+                .unwrap()
+                .contains(&channel.user_channel_id.0)
+            {
+                continue;
+            }
+            let webhooks = state
+                .lsps5_webhooks
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|w| w.client_node_id == channel.counterparty_node_id)
+                .cloned()
+                .collect::<Vec<_>>();
+            notify(&state, webhooks, PAYMENT_INCOMING);
+        }
+    }
+}
+
+pub(crate) async fn sink_delivery(
+    State(state): State<AppState>,
+    Path(tag): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> ApiResult<Value> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+    };
+    let delivery = WebhookDelivery {
+        tag,
+        received_at: unix_now(),
+        timestamp: header("x-lsps5-timestamp"),
+        signature: header("x-lsps5-signature"),
+        body,
+    };
+    state.webhook_deliveries.lock().unwrap().push(delivery);
+
+    Ok(Json(json!({"received": true})))
+}
+
+pub(crate) async fn list_deliveries(
+    State(state): State<AppState>,
+) -> ApiResult<ListWebhookDeliveriesResponse> {
+    let deliveries = state.webhook_deliveries.lock().unwrap().clone();
+    Ok(Json(ListWebhookDeliveriesResponse { deliveries }))
+}
+
+pub(crate) async fn clear_deliveries(State(state): State<AppState>) -> ApiResult<Value> {
+    state.webhook_deliveries.lock().unwrap().clear();
+    Ok(Json(json!({"cleared": true})))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::iso8601;
+
+    #[test]
+    fn iso8601_formats_unix_timestamps() {
+        assert_eq!(iso8601(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(iso8601(1_704_067_199), "2023-12-31T23:59:59.000Z");
+        assert_eq!(iso8601(1_709_210_096), "2024-02-29T12:34:56.000Z");
+        assert_eq!(iso8601(951_782_400), "2000-02-29T00:00:00.000Z");
+    }
+}
@@ -1,27 +1,53 @@
+mod events;
+mod hold_invoices;
+mod lsps1;
+mod lsps5;
+
+use std::collections::{HashMap, HashSet};
 use std::process::Command;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use axum::extract::{Path, State};
-use axum::routing::post;
+use axum::extract::{Path, Query, State};
+use axum::routing::{delete, post, put};
 use axum::Json;
 use axum::{routing::get, Router};
 use electrsd::bitcoind::bitcoincore_rpc::RpcApi;
-use hex::FromHex;
+use hex::{DisplayHex, FromHex};
+use ldk_node::bitcoin::hashes::Hash;
+use ldk_node::bitcoin::secp256k1::PublicKey;
+use ldk_node::bitcoin::FeeRate;
+use ldk_node::config::ChannelConfig;
 use ldk_node::lightning::ln::channelmanager::PaymentId;
 use ldk_node::lightning::ln::msgs::SocketAddress;
+use ldk_node::lightning::offers::offer::Offer;
+use ldk_node::lightning::offers::refund::Refund;
 use ldk_node::lightning_invoice::Bolt11Invoice;
-use ldk_node::Event;
 use ldk_node::Node;
+use ldk_node::UserChannelId;
 use ldk_node::{bitcoin::Network, Builder};
+use ldk_node::{BalanceDetails, ChannelDetails, CustomTlvRecord};
 use lspsd::client::LspsClient;
+use lspsd::error::{ApiError, ApiResult};
 use serde_json::{json, Value};
+use tokio::sync::broadcast;
 
 use argh::FromArgs;
+use events::EventTracker;
 use lspsd::{
-    utils, FaucetRequest, FundingAddress, GetBalanceResponse, GetInvoiceRequest,
-    GetInvoiceResponse, GetPaymentResponse, ListChannelsResponse, LspConfig, OpenChannelRequest,
-    OpenChannelResponse, PayInvoiceRequest, PayInvoiceResponse,
+    utils, ChainTip, ChannelBalance, ChannelConfigUpdate, ChannelState, ChannelStatus,
+    CloseChannelRequest, CloseChannelResponse, ClosedChannel, CompactChannel, ConfigFile,
+    ConnectPeerRequest, FaucetRequest, FundingAddress, GetBalanceResponse, GetInvoiceRequest,
+    GetInvoiceResponse, GetOfferRequest, GetOfferResponse, GetPaymentResponse,
+    InitiateRefundRequest, InitiateRefundResponse, JitInvoiceRequest, KeysendRequest,
+    KeysendResponse, ListChannelsQuery, ListChannelsResponse, ListClosedChannelsResponse,
+    ListPaymentsQuery, ListPaymentsResponse, ListPeersResponse, LspConfig, Lsps1Config, Lsps1Order,
+    Lsps2Config, Lsps5Webhook, MineBlocksRequest, MineBlocksResponse, NodeEvent,
+    OnchainSendRequest, OnchainSendResponse, OpenChannelRequest, OpenChannelResponse,
+    PayInvoiceRequest, PayInvoiceResponse, PayOfferRequest, PayOfferResponse, Payment, Peer,
+    PendingSweep, ReorgRequest, ReorgResponse, RequestRefundRequest, RequestRefundResponse,
+    SweepStatus, WebhookDelivery,
 };
 
 #[derive(FromArgs)]
@@ -45,21 +71,198 @@ struct LspArgs {
     /// what rgs server to use
     #[argh(option)]
     rgs_url: Option<String>,
+    /// alias announced to the network
+    #[argh(option)]
+    node_alias: Option<String>,
+    /// address announced to the network, can be repeated
+    #[argh(option)]
+    announcement_address: Vec<SocketAddress>,
+    /// node id of a peer whose channels are accepted as zero-conf, can be repeated
+    #[argh(option)]
+    trusted_peers_0conf: Vec<PublicKey>,
+    /// let the spawned payer node accept zero-conf channels from this node
+    #[argh(switch)]
+    payer_trusts_lsp_0conf: bool,
     /// optional lspsd faucet to get funds from
     #[argh(option)]
     lspsd_faucet_url: Option<String>,
+    /// optional json config file, command line options take precedence
+    #[argh(option)]
+    config_file: Option<String>,
+    /// token lsps2 clients must provide
+    #[argh(option)]
+    lsps2_require_token: Option<String>,
+    /// require lsps2 clients to provide a token, generating one if none is given. The node accepts
+    /// this single token only, it can't mint, expire or revoke others
+    #[argh(switch)]
+    lsps2_token_required: bool,
+    /// whether to advertise the lsps2 service
+    #[argh(option)]
+    lsps2_advertise_service: Option<bool>,
+    /// lsps2 proportional channel opening fee
+    #[argh(option)]
+    lsps2_channel_opening_fee_ppm: Option<u32>,
+    /// lsps2 channel over-provisioning
+    #[argh(option)]
+    lsps2_channel_over_provisioning_ppm: Option<u32>,
+    /// lsps2 minimum channel opening fee
+    #[argh(option)]
+    lsps2_min_channel_opening_fee_msat: Option<u64>,
+    /// lsps2 minimum channel lifetime in blocks
+    #[argh(option)]
+    lsps2_min_channel_lifetime: Option<u32>,
+    /// lsps2 maximum client to_self_delay
+    #[argh(option)]
+    lsps2_max_client_to_self_delay: Option<u32>,
+    /// lsps2 minimum payment size
+    #[argh(option)]
+    lsps2_min_payment_size_msat: Option<u64>,
+    /// lsps2 maximum payment size
+    #[argh(option)]
+    lsps2_max_payment_size_msat: Option<u64>,
+    /// node id of an lsps2 service to request jit channels from
+    #[argh(option)]
+    lsps2_source_node_id: Option<PublicKey>,
+    /// address of the lsps2 service to request jit channels from
+    #[argh(option)]
+    lsps2_source_address: Option<SocketAddress>,
+    /// token to provide to the lsps2 service
+    #[argh(option)]
+    lsps2_source_token: Option<String>,
+    /// enable the lsps1 service
+    #[argh(switch)]
+    lsps1: bool,
+    /// lsps1 minimum inbound liquidity per order
+    #[argh(option)]
+    lsps1_min_initial_lsp_balance_sat: Option<u64>,
+    /// lsps1 maximum inbound liquidity per order
+    #[argh(option)]
+    lsps1_max_initial_lsp_balance_sat: Option<u64>,
+    /// lsps1 minimum client balance per order
+    #[argh(option)]
+    lsps1_min_initial_client_balance_sat: Option<u64>,
+    /// lsps1 maximum client balance per order
+    #[argh(option)]
+    lsps1_max_initial_client_balance_sat: Option<u64>,
+    /// lsps1 maximum channel lifetime in blocks
+    #[argh(option)]
+    lsps1_max_channel_expiry_blocks: Option<u32>,
+    /// lsps1 fixed order fee
+    #[argh(option)]
+    lsps1_fee_base_sat: Option<u64>,
+    /// lsps1 proportional order fee
+    #[argh(option)]
+    lsps1_fee_ppm: Option<u32>,
+    /// lsps1 seconds an order can be paid for
+    #[argh(option)]
+    lsps1_order_expiry_secs: Option<u32>,
 }
+
+impl LspArgs {
+    /// Loads the config file, if any, and applies the command line overrides on top of it
+    fn config_file(&self) -> ConfigFile {
+        let mut config = match &self.config_file {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path).expect("failed to read config file");
+                serde_json::from_str(&contents).expect("failed to parse config file")
+            }
+            None => ConfigFile::default(),
+        };
+
+        let lsps2 = &mut config.lsps2;
+        if let Some(token) = &self.lsps2_require_token {
+            lsps2.require_token = Some(token.clone());
+        }
+        if self.lsps2_token_required && lsps2.require_token.is_none() {
+            lsps2.require_token = Some(random_id());
+        }
+        if let Some(advertise_service) = self.lsps2_advertise_service {
+            lsps2.advertise_service = advertise_service;
+        }
+        if let Some(fee_ppm) = self.lsps2_channel_opening_fee_ppm {
+            lsps2.channel_opening_fee_ppm = fee_ppm;
+        }
+        if let Some(over_provisioning_ppm) = self.lsps2_channel_over_provisioning_ppm {
+            lsps2.channel_over_provisioning_ppm = over_provisioning_ppm;
+        }
+        if let Some(min_fee_msat) = self.lsps2_min_channel_opening_fee_msat {
+            lsps2.min_channel_opening_fee_msat = min_fee_msat;
+        }
+        if let Some(min_lifetime) = self.lsps2_min_channel_lifetime {
+            lsps2.min_channel_lifetime = min_lifetime;
+        }
+        if let Some(max_delay) = self.lsps2_max_client_to_self_delay {
+            lsps2.max_client_to_self_delay = max_delay;
+        }
+        if let Some(min_payment_msat) = self.lsps2_min_payment_size_msat {
+            lsps2.min_payment_size_msat = min_payment_msat;
+        }
+        if let Some(max_payment_msat) = self.lsps2_max_payment_size_msat {
+            lsps2.max_payment_size_msat = max_payment_msat;
+        }
+
+        if self.lsps1 && config.lsps1.is_none() {
+            config.lsps1 = Some(Lsps1Config::default());
+        }
+        if let Some(lsps1) = &mut config.lsps1 {
+            if let Some(min_lsp_balance) = self.lsps1_min_initial_lsp_balance_sat {
+                lsps1.min_initial_lsp_balance_sat = min_lsp_balance;
+            }
+            if let Some(max_lsp_balance) = self.lsps1_max_initial_lsp_balance_sat {
+                lsps1.max_initial_lsp_balance_sat = max_lsp_balance;
+            }
+            if let Some(min_client_balance) = self.lsps1_min_initial_client_balance_sat {
+                lsps1.min_initial_client_balance_sat = min_client_balance;
+            }
+            if let Some(max_client_balance) = self.lsps1_max_initial_client_balance_sat {
+                lsps1.max_initial_client_balance_sat = max_client_balance;
+            }
+            if let Some(max_expiry) = self.lsps1_max_channel_expiry_blocks {
+                lsps1.max_channel_expiry_blocks = max_expiry;
+            }
+            if let Some(fee_base_sat) = self.lsps1_fee_base_sat {
+                lsps1.fee_base_sat = fee_base_sat;
+            }
+            if let Some(fee_ppm) = self.lsps1_fee_ppm {
+                lsps1.fee_ppm = fee_ppm;
+            }
+            if let Some(order_expiry_secs) = self.lsps1_order_expiry_secs {
+                lsps1.order_expiry_secs = order_expiry_secs;
+            }
+        }
+
+        config
+    }
+}
+
 #[derive(Clone)]
 struct AppState {
     node: Arc<Node>,
     bitcoin: Option<Arc<electrsd::bitcoind::BitcoinD>>,
     esplora: Option<Arc<electrsd::ElectrsD>>,
+    lsps2: Lsps2Config,
+    lsps1: Option<Lsps1Config>,
+    lsps1_orders: Arc<Mutex<HashMap<String, Lsps1Order>>>,
+    lsps5_webhooks: Arc<Mutex<Vec<Lsps5Webhook>>>,
+    webhook_deliveries: Arc<Mutex<Vec<WebhookDelivery>>>,
+    events: broadcast::Sender<NodeEvent>,
+    tracker: Arc<EventTracker>,
+    /// Channels we started closing, they stay listed until the close is negotiated
+    closing_channels: Arc<Mutex<HashSet<u128>>>,
+    /// Channels opened through the http api or for LSPS1 orders, the node opens any other
+    /// outbound channel as an LSPS2 JIT channel
+    api_channels: Arc<Mutex<HashSet<u128>>>,
+    /// Peers whose channels we accept as zero-conf
+    trusted_peers_0conf: Vec<PublicKey>,
+    /// Api of the payer node we spawned, if any
+    payer_api_url: Option<String>,
 }
 
 fn main() {
     let rt = Arc::new(tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap());
 
     let args: LspArgs = argh::from_env();
+    let config_file = args.config_file();
 
     let data_dir = args.data_dir.unwrap_or("lsp".to_string());
     let lightning_port = args.lightning_port.unwrap_or(9735);
@@ -72,6 +275,7 @@ fn main() {
             addr: [0, 0, 0, 0],
             port: lightning_port,
         }]),
+        trusted_peers_0conf: args.trusted_peers_0conf.clone(),
         ..Default::default()
     };
 
@@ -104,17 +308,23 @@ fn main() {
 
     let mut builder = Builder::from_config(config);
     builder.set_chain_source_esplora(esplora_url.clone(), None);
-    builder.set_liquidity_provider_lsps2(ldk_node::liquidity::LSPS2ServiceConfig {
-        require_token: None,
-        advertise_service: true,
-        channel_opening_fee_ppm: 0,
-        channel_over_provisioning_ppm: 500_000,
-        min_channel_opening_fee_msat: 0,
-        min_channel_lifetime: 100,
-        max_client_to_self_delay: 72,
-        min_payment_size_msat: 1000,
-        max_payment_size_msat: 100_000_000_000,
-    });
+    builder.set_liquidity_provider_lsps2(config_file.lsps2.clone().into());
+    if let (Some(node_id), Some(address)) =
+        (args.lsps2_source_node_id, args.lsps2_source_address.clone())
+    {
+        builder.set_liquidity_source_lsps2(node_id, address, args.lsps2_source_token.clone());
+    }
+
+    if let Some(node_alias) = args.node_alias {
+        builder
+            .set_node_alias(node_alias)
+            .expect("invalid node alias");
+    }
+    if !args.announcement_address.is_empty() {
+        builder
+            .set_announcement_addresses(args.announcement_address)
+            .expect("invalid announcement addresses");
+    }
 
     if let Some(rgs_url) = args.rgs_url {
         builder.set_gossip_source_rgs(rgs_url);
@@ -126,6 +336,8 @@ fn main() {
 
     node.start().unwrap();
 
+    let mut payer_api_url = None;
+
     // if no esplora url was given, then we started our own so lets fund ourselves
     if let (Some(bitcoin), Some(esplora)) = (&bitcoin, &esplora) {
 
@@ -177,10 +389,13 @@ fn main() {
         let child_lightning_port = format!("{}", lightning_port + 1);
         let child_api_port = format!("{}", api_port + 1);
         let lspsd_faucet_url = format!("http://localhost:{}", api_port);
+        // the payer gets its jit channels from us
+        let lsps2_source_node_id = node.node_id().to_string();
+        let lsps2_source_address = format!("127.0.0.1:{}", lightning_port);
 
         println!("LSP Node API URL: http://localhost:{}", api_port);
 
-        let child_args = vec![
+        let mut child_args = vec![
             "--data-dir",
             &child_data_dir,
             "--lightning-port",
@@ -191,12 +406,25 @@ fn main() {
             &esplora_url,
             "--lspsd-faucet-url",
             &lspsd_faucet_url,
+            "--lsps2-source-node-id",
+            &lsps2_source_node_id,
+            "--lsps2-source-address",
+            &lsps2_source_address,
         ];
         
+        if let Some(token) = &config_file.lsps2.require_token {
+            child_args.extend(["--lsps2-source-token", token.as_str()]);
+        }
+        if args.payer_trusts_lsp_0conf {
+            // lets us open zero-conf channels to the payer
+            child_args.extend(["--trusted-peers-0conf", lsps2_source_node_id.as_str()]);
+        }
+
         let _child = Command::new(std::env::current_exe().unwrap())
             .args(&child_args)
             .spawn()
             .expect("failed to spawn child process");
+        payer_api_url = Some(format!("http://localhost:{}", child_api_port));
     } else {
         println!("Payer Node API URL: http://localhost:{}", api_port);
     }
@@ -221,18 +449,83 @@ fn main() {
         node: Arc::new(node),
         bitcoin,
         esplora,
+        lsps2: config_file.lsps2,
+        lsps1: config_file.lsps1,
+        lsps1_orders: Arc::new(Mutex::new(HashMap::new())),
+        lsps5_webhooks: Arc::new(Mutex::new(Vec::new())),
+        webhook_deliveries: Arc::new(Mutex::new(Vec::new())),
+        events: broadcast::channel(events::EVENT_CHANNEL_CAPACITY).0,
+        tracker: Arc::new(EventTracker::default()),
+        closing_channels: Arc::new(Mutex::new(HashSet::new())),
+        api_channels: Arc::new(Mutex::new(HashSet::new())),
+        trusted_peers_0conf: args.trusted_peers_0conf,
+        payer_api_url,
     };
+    rt.spawn(events::process_events(app_state.clone()));
+    rt.spawn(lsps5::watch_channels(app_state.clone()));
+
     let app = Router::new()
         .route("/config", get(config_handler))
         .route("/funding-address", get(funding_address))
         .route("/faucet", post(faucet))
+        .route("/onchain/send", post(onchain_send))
         .route("/channels", post(open_channel))
+        .route("/peers", post(connect_peer).get(list_peers))
+        .route("/peers/{pubkey}", delete(disconnect_peer))
+        .route("/channels/closed", get(list_closed_channels))
+        .route("/channels/{user_channel_id}", get(get_channel))
+        .route("/channels/{user_channel_id}/close", post(close_channel))
+        .route(
+            "/channels/{user_channel_id}/config",
+            put(update_channel_config),
+        )
+        .route(
+            "/channels/{user_channel_id}/force-close",
+            post(force_close_channel),
+        )
         .route("/channels", get(list_channels))
         .route("/pay-invoice", post(pay_invoice))
         .route("/get-invoice", post(get_invoice))
+        .route("/jit-invoice", post(jit_invoice))
+        .route("/hold-invoices", post(hold_invoices::create_hold_invoice))
+        .route(
+            "/hold-invoices/held",
+            get(hold_invoices::list_held_payments),
+        )
+        .route(
+            "/hold-invoices/{payment_hash}/claim",
+            post(hold_invoices::claim_hold_invoice),
+        )
+        .route(
+            "/hold-invoices/{payment_hash}/fail",
+            post(hold_invoices::fail_hold_invoice),
+        )
+        .route("/get-offer", post(get_offer))
+        .route("/pay-offer", post(pay_offer))
+        .route("/initiate-refund", post(initiate_refund))
+        .route("/request-refund", post(request_refund))
+        .route("/keysend", post(keysend))
         .route("/sync", post(sync))
+        .route("/chain/mine", post(mine_blocks))
+        .route("/chain/tip", get(get_chain_tip))
+        .route("/chain/reorg", post(reorg))
         .route("/balance", get(get_balance))
         .route("/get-payment/{payment_hash}", get(get_payment))
+        .route("/payments", get(list_payments))
+        .route("/lsps1/info", get(lsps1::get_info))
+        .route("/lsps1/orders", post(lsps1::create_order))
+        .route("/lsps1/orders", get(lsps1::list_orders))
+        .route("/lsps1/orders/{order_id}", get(lsps1::get_order))
+        .route("/lsps5/webhooks", post(lsps5::set_webhook))
+        .route("/lsps5/webhooks", get(lsps5::list_webhooks))
+        .route(
+            "/lsps5/webhooks/{client_node_id}/{app_name}",
+            delete(lsps5::remove_webhook),
+        )
+        .route("/webhook-sink/{tag}", post(lsps5::sink_delivery))
+        .route("/webhook-sink", get(lsps5::list_deliveries))
+        .route("/webhook-sink", delete(lsps5::clear_deliveries))
+        .route("/events", get(events::events_handler))
         .with_state(app_state);
 
     rt.block_on(async {
@@ -243,176 +536,1306 @@ fn main() {
     });
 }
 
-async fn config_handler(State(state): State<AppState>) -> Json<LspConfig> {
+async fn config_handler(State(state): State<AppState>) -> ApiResult<LspConfig> {
+    let ip_port = state
+        .node
+        .listening_addresses()
+        .and_then(|addresses| addresses.first().cloned())
+        .ok_or_else(|| ApiError::internal("not_listening", "node has no listening address"))?;
     let lsp_config = LspConfig {
         pubkey: state.node.node_id(),
-        ip_port: state.node.listening_addresses().unwrap()[0].to_string(),
-        token: None,
+        ip_port: ip_port.to_string(),
+        token: state.lsps2.require_token.clone(),
+        lsps2: state.lsps2.clone(),
+        lsps1: state.lsps1.clone(),
+        trusted_peers_0conf: state.trusted_peers_0conf.clone(),
     };
 
-    Json(lsp_config)
+    Ok(Json(lsp_config))
 }
 
-async fn funding_address(State(state): State<AppState>) -> Json<FundingAddress> {
-    Json(FundingAddress {
-        address: state
+async fn funding_address(State(state): State<AppState>) -> ApiResult<FundingAddress> {
+    Ok(Json(FundingAddress {
+        address: state.node.onchain_payment().new_address()?.to_string(),
+    }))
+}
+
+/// Amount sent by the faucet when the request doesn't specify one
+const FAUCET_DEFAULT_SATS: u64 = 100_000_000;
+
+/// Headroom for fees when checking whether our wallet can pay the faucet itself
+const FAUCET_FEE_BUFFER_SATS: u64 = 10_000;
+
+async fn faucet(
+    State(state): State<AppState>,
+    Json(req): Json<FaucetRequest>,
+) -> ApiResult<String> {
+    let address = ldk_node::bitcoin::Address::from_str(&req.address)
+        .map_err(|e| {
+            ApiError::bad_request("invalid_address", "could not parse address").with_details(e)
+        })?
+        .assume_checked();
+    let amount_sats = req.amount_sats.unwrap_or(FAUCET_DEFAULT_SATS);
+    let fee_rate = parse_fee_rate(req.fee_rate_sat_per_vb)?;
+    // without our own bitcoind the default confirmation is skipped, only explicit ones fail
+    if matches!(req.confirmations, Some(1..)) && state.bitcoin.is_none() {
+        return Err(mining_unavailable());
+    }
+
+    let spendable_sats = state.node.list_balances().spendable_onchain_balance_sats;
+    let txid = match &state.bitcoin {
+        Some(bitcoin) if spendable_sats < amount_sats + FAUCET_FEE_BUFFER_SATS => {
+            send_from_bitcoind(bitcoin, &address, amount_sats, req.fee_rate_sat_per_vb)?
+        }
+        _ => state
             .node
             .onchain_payment()
-            .new_address()
-            .unwrap()
+            .send_to_address(&address, amount_sats, fee_rate)?
             .to_string(),
-    })
+    };
+
+    let confirmations = req.confirmations.unwrap_or(1);
+    if confirmations > 0 && state.bitcoin.is_some() {
+        mine_and_sync(&state, confirmations)?;
+    }
+    Ok(Json(txid))
 }
 
-async fn faucet(State(state): State<AppState>, Json(req): Json<FaucetRequest>) -> Json<String> {
+/// Pays from bitcoind's own wallet, used when our wallet runs low
+fn send_from_bitcoind(
+    bitcoin: &electrsd::bitcoind::BitcoinD,
+    address: &ldk_node::bitcoin::Address,
+    amount_sats: u64,
+    fee_rate_sat_per_vb: Option<u64>,
+) -> Result<String, ApiError> {
+    // positional arguments of sendtoaddress, nulls fall back to bitcoind's defaults
+    let args = [
+        json!(address.to_string()),
+        json!(ldk_node::bitcoin::Amount::from_sat(amount_sats).to_btc()),
+        Value::Null,
+        Value::Null,
+        json!(false),
+        json!(true),
+        Value::Null,
+        json!("unset"),
+        Value::Null,
+        json!(fee_rate_sat_per_vb),
+    ];
+    let txid = bitcoin.client.call::<String>("sendtoaddress", &args)?;
+    Ok(txid)
+}
+
+fn parse_fee_rate(sat_per_vb: Option<u64>) -> Result<Option<FeeRate>, ApiError> {
+    sat_per_vb
+        .map(|sat_per_vb| {
+            FeeRate::from_sat_per_vb(sat_per_vb).ok_or_else(|| {
+                ApiError::bad_request("invalid_fee_rate", "fee rate is too high")
+                    .with_details(sat_per_vb)
+            })
+        })
+        .transpose()
+}
+
+async fn onchain_send(
+    State(state): State<AppState>,
+    Json(req): Json<OnchainSendRequest>,
+) -> ApiResult<OnchainSendResponse> {
     let address = ldk_node::bitcoin::Address::from_str(&req.address)
-        .unwrap()
+        .map_err(|e| {
+            ApiError::bad_request("invalid_address", "could not parse address").with_details(e)
+        })?
         .assume_checked();
-    let txid = state
-        .node
-        .onchain_payment()
-        .send_to_address(&address, 100_000_000, None)
-        .unwrap();
+    let fee_rate = parse_fee_rate(req.fee_rate_sat_per_vb)?;
+    if req.mine_blocks > 0 && state.bitcoin.is_none() {
+        return Err(mining_unavailable());
+    }
 
-    if let Some(esplora) = &state.esplora {
-        if let Some(bitcoin) = &state.bitcoin {
-            let miner_address = bitcoin
-                .client
-                .get_new_address(None, None)
-                .unwrap()
-                .assume_checked();
-            bitcoin
-                .client
-                .generate_to_address(1, &miner_address)
-                .unwrap();
-            let info = bitcoin.client.get_blockchain_info().unwrap();
-            esplora.wait_height(info.blocks as usize);
+    let onchain_payment = state.node.onchain_payment();
+    let txid = match (req.send_all, req.amount_sats) {
+        (true, None) => {
+            onchain_payment.send_all_to_address(&address, req.retain_reserve, fee_rate)?
+        }
+        (false, Some(amount_sats)) => {
+            onchain_payment.send_to_address(&address, amount_sats, fee_rate)?
         }
+        _ => {
+            return Err(ApiError::bad_request(
+                "invalid_amount",
+                "either amount_sats or send_all must be set",
+            ));
+        }
+    };
+
+    // ldk-node only returns the txid, the payment store learns the fee once a wallet sync sees
+    // the transaction. Mining already syncs, otherwise sync once and leave the fee unset if
+    // esplora hasn't seen the transaction yet
+    if req.mine_blocks > 0 {
+        mine_and_sync(&state, req.mine_blocks)?;
+    } else {
+        state.node.sync_wallets()?;
     }
-    Json(txid.to_string())
+
+    // onchain payments are stored under their txid
+    let fee_sats = state
+        .node
+        .payment(&PaymentId(txid.to_byte_array()))
+        .and_then(|payment| payment.fee_paid_msat)
+        .map(|fee_msat| fee_msat / 1000);
+
+    Ok(Json(OnchainSendResponse {
+        txid: txid.to_string(),
+        fee_sats,
+    }))
 }
 
 async fn open_channel(
     State(state): State<AppState>,
     Json(req): Json<OpenChannelRequest>,
-) -> Json<OpenChannelResponse> {
-    let socket_addr = SocketAddress::from_str(&req.ip_port).unwrap();
-    let res = state
-        .node
-        .open_channel(
-            req.pubkey,
+) -> ApiResult<OpenChannelResponse> {
+    let (pubkey, socket_addr) = parse_peer_address(Some(req.pubkey), &req.ip_port)?;
+    if req.zero_conf {
+        check_payer_trusts_us(&state, pubkey)?;
+    }
+    let channel_config = req.channel_config.as_ref().map(|update| {
+        let mut config = ChannelConfig::default();
+        update.apply(&mut config);
+        config
+    });
+    let push_msat = Some(sats_to_msat("push_sats", req.push_sats)?);
+    let user_channel_id = if req.announce {
+        state.node.open_announced_channel(
+            pubkey,
             socket_addr,
             req.funding_sats,
-            Some(req.push_sats * 1000),
-            None,
-        )
-        .unwrap();
-
-    if let Some(esplora) = &state.esplora {
-        if let Some(bitcoin) = &state.bitcoin {
-            loop {
-                let event = state.node.wait_next_event();
-
-                if let Event::ChannelPending { .. } = event {
-                    let miner_address = bitcoin
-                        .client
-                        .get_new_address(None, None)
-                        .unwrap()
-                        .assume_checked();
-                    bitcoin
-                        .client
-                        .generate_to_address(6, &miner_address)
-                        .unwrap();
-                    let info = bitcoin.client.get_blockchain_info().unwrap();
-                    esplora.wait_height(info.blocks as usize);
-                    state.node.sync_wallets().unwrap();
-                }
-
-                if let Event::ChannelReady { .. } = event {
-                    if let Err(e) = state.node.event_handled() {
-                        println!("failed to handle channel ready event: {}", e);
-                    }
-                    break;
-                }
+            push_msat,
+            channel_config,
+        )?
+    } else {
+        state.node.open_channel(
+            pubkey,
+            socket_addr,
+            req.funding_sats,
+            push_msat,
+            channel_config,
+        )?
+    }
+    .0;
+    state.api_channels.lock().unwrap().insert(user_channel_id);
 
-                if let Err(e) = state.node.event_handled() {
-                    println!("failed to handle event: {}", e);
-                }
+    let (zero_conf, confirmations) = (req.zero_conf, req.confirmations);
+    let ready = {
+        let state = state.clone();
+        async move {
+            if zero_conf {
+                ready_zero_conf_channel(&state, user_channel_id).await
+            } else {
+                confirm_channel(&state, user_channel_id, confirmations).await
             }
         }
+    };
+
+    if !req.wait {
+        tokio::spawn(async move {
+            if let Err(e) = ready.await {
+                println!("channel {} was not confirmed: {}", user_channel_id, e);
+            }
+        });
+        return Ok(Json(OpenChannelResponse {
+            user_channel_id,
+            funding_txo: None,
+        }));
     }
 
-    Json(OpenChannelResponse {
-        user_channel_id: res.0,
+    ready.await?;
+    Ok(Json(OpenChannelResponse {
+        user_channel_id,
+        funding_txo: find_channel(&state, user_channel_id)
+            .and_then(|channel| channel.funding_txo)
+            .map(|txo| txo.to_string()),
+    }))
+}
+
+fn find_channel(state: &AppState, user_channel_id: u128) -> Option<ChannelDetails> {
+    state
+        .node
+        .list_channels()
+        .into_iter()
+        .find(|channel| channel.user_channel_id.0 == user_channel_id)
+}
+
+async fn update_channel_config(
+    State(state): State<AppState>,
+    Path(user_channel_id): Path<u128>,
+    Json(req): Json<ChannelConfigUpdate>,
+) -> ApiResult<CompactChannel> {
+    let channel = find_channel(&state, user_channel_id).ok_or_else(|| {
+        ApiError::not_found(
+            "channel_not_found",
+            format!("no channel with user_channel_id {}", user_channel_id),
+        )
+    })?;
+
+    let mut config = channel.config;
+    req.apply(&mut config);
+    state.node.update_channel_config(
+        &channel.user_channel_id,
+        channel.counterparty_node_id,
+        config,
+    )?;
+
+    let channel = find_channel(&state, user_channel_id).unwrap_or(channel);
+    Ok(Json(channel.into()))
+}
+
+async fn get_channel(
+    State(state): State<AppState>,
+    Path(user_channel_id): Path<u128>,
+) -> ApiResult<ChannelStatus> {
+    if let Some(channel) = find_channel(&state, user_channel_id) {
+        let closing = state
+            .closing_channels
+            .lock()
+            .unwrap()
+            .contains(&user_channel_id);
+        let channel_state = if closing {
+            ChannelState::Closing
+        } else if channel.is_channel_ready {
+            ChannelState::Ready
+        } else if channel.funding_txo.is_some() {
+            ChannelState::AwaitingConfirmations
+        } else {
+            ChannelState::Pending
+        };
+
+        return Ok(Json(ChannelStatus {
+            user_channel_id,
+            state: channel_state,
+            channel_id: Some(channel.channel_id.to_string()),
+            counterparty_node_id: Some(channel.counterparty_node_id),
+            funding_txo: channel.funding_txo.map(|txo| txo.to_string()),
+            confirmations: channel.confirmations,
+            confirmations_required: channel.confirmations_required,
+            closure_reason: None,
+        }));
+    }
+
+    // closed channels are only known from their closing event
+    let closed = state
+        .tracker
+        .closed_channels()
+        .into_iter()
+        .find_map(|event| match event {
+            NodeEvent::ChannelClosed {
+                channel_id,
+                user_channel_id: id,
+                counterparty_node_id,
+                reason,
+            } if id == user_channel_id => Some((channel_id, counterparty_node_id, reason)),
+            _ => None,
+        });
+    let Some((channel_id, counterparty_node_id, reason)) = closed else {
+        return Err(ApiError::not_found(
+            "channel_not_found",
+            format!("no channel with user_channel_id {}", user_channel_id),
+        ));
+    };
+
+    let (sweep_status, _) = sweep_status(&state.node.list_balances(), &channel_id);
+    Ok(Json(ChannelStatus {
+        user_channel_id,
+        state: if sweep_status == SweepStatus::Swept {
+            ChannelState::Closed
+        } else {
+            ChannelState::Closing
+        },
+        channel_id: Some(channel_id),
+        counterparty_node_id,
+        funding_txo: None,
+        confirmations: None,
+        confirmations_required: None,
+        closure_reason: reason,
+    }))
+}
+
+/// Parses a `host:port` address or a `pubkey@host:port` uri, whose pubkey must match `pubkey`
+fn parse_peer_address(
+    pubkey: Option<PublicKey>,
+    address: &str,
+) -> Result<(PublicKey, SocketAddress), ApiError> {
+    let (uri_pubkey, address) = match address.split_once('@') {
+        Some((uri_pubkey, address)) => {
+            let uri_pubkey = PublicKey::from_str(uri_pubkey).map_err(|e| {
+                ApiError::bad_request("invalid_public_key", "could not parse the uri pubkey")
+                    .with_details(e)
+            })?;
+            (Some(uri_pubkey), address)
+        }
+        None => (None, address),
+    };
+    let socket_addr = SocketAddress::from_str(address).map_err(|e| {
+        ApiError::bad_request("invalid_socket_address", "could not parse peer address")
+            .with_details(e)
+    })?;
+
+    let pubkey = match (pubkey, uri_pubkey) {
+        (Some(pubkey), Some(uri_pubkey)) if pubkey != uri_pubkey => {
+            return Err(ApiError::bad_request(
+                "pubkey_mismatch",
+                "the uri pubkey does not match pubkey",
+            ));
+        }
+        (Some(pubkey), _) | (None, Some(pubkey)) => pubkey,
+        (None, None) => {
+            return Err(ApiError::bad_request(
+                "missing_pubkey",
+                "either pubkey or a pubkey@host:port uri is required",
+            ));
+        }
+    };
+
+    Ok((pubkey, socket_addr))
+}
+
+async fn connect_peer(
+    State(state): State<AppState>,
+    Json(req): Json<ConnectPeerRequest>,
+) -> ApiResult<Peer> {
+    let (pubkey, socket_addr) = parse_peer_address(req.pubkey, &req.address)?;
+    state.node.connect(pubkey, socket_addr, req.persist)?;
+
+    let peer = state
+        .node
+        .list_peers()
+        .into_iter()
+        .find(|peer| peer.node_id == pubkey)
+        .ok_or_else(|| ApiError::internal("peer_not_found", "connected peer is not listed"))?;
+    Ok(Json(peer.into()))
+}
+
+async fn disconnect_peer(
+    State(state): State<AppState>,
+    Path(pubkey): Path<String>,
+) -> ApiResult<Value> {
+    let pubkey = PublicKey::from_str(&pubkey).map_err(|e| {
+        ApiError::bad_request("invalid_public_key", "could not parse pubkey").with_details(e)
+    })?;
+    state.node.disconnect(pubkey)?;
+    Ok(Json(json!({"disconnected": true})))
+}
+
+async fn list_peers(State(state): State<AppState>) -> ApiResult<ListPeersResponse> {
+    let peers = state
+        .node
+        .list_peers()
+        .into_iter()
+        .map(|peer| peer.into())
+        .collect::<Vec<_>>();
+
+    Ok(Json(ListPeersResponse { peers }))
+}
+
+/// Converts a sats amount from a request to msat, rejecting amounts that don't fit
+pub(crate) fn sats_to_msat(field: &str, sats: u64) -> Result<u64, ApiError> {
+    sats.checked_mul(1000).ok_or_else(|| {
+        ApiError::bad_request("invalid_amount", format!("{} is too large", field))
+            .with_details(sats)
     })
 }
 
-async fn list_channels(State(state): State<AppState>) -> Json<ListChannelsResponse> {
+/// Random 16 byte hex identifier
+fn random_id() -> String {
+    rand::random::<[u8; 16]>().to_lower_hex_string()
+}
+
+/// Current unix timestamp in seconds
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Blocks mined to confirm channels opened by the LSPS1 service
+const DEFAULT_CHANNEL_CONFIRMATIONS: u64 = 6;
+
+/// Opens a channel and, when running against our own bitcoind, mines it until it is ready
+async fn open_channel_and_mine(
+    state: &AppState,
+    pubkey: PublicKey,
+    socket_addr: SocketAddress,
+    funding_sats: u64,
+    push_msat: u64,
+) -> Result<UserChannelId, ApiError> {
+    let res = state
+        .node
+        .open_channel(pubkey, socket_addr, funding_sats, Some(push_msat), None)?;
+    state.api_channels.lock().unwrap().insert(res.0);
+    confirm_channel(state, res.0, DEFAULT_CHANNEL_CONFIRMATIONS).await?;
+    Ok(res)
+}
+
+/// Waits for the channel to be pending and, when running against our own bitcoind, mines `blocks`
+/// blocks on top of its funding transaction, then waits for it to be ready if that was enough
+async fn confirm_channel(
+    state: &AppState,
+    user_channel_id: u128,
+    blocks: u64,
+) -> Result<(), ApiError> {
+    // any event means the channel is at least pending
+    let event = state
+        .tracker
+        .wait_for_channel(user_channel_id, |_| true)
+        .await;
+    check_not_closed(&event)?;
+
+    if state.bitcoin.is_none() || blocks == 0 {
+        return Ok(());
+    }
+    mine_and_sync(state, blocks)?;
+
+    let confirmations_required = find_channel(state, user_channel_id)
+        .and_then(|channel| channel.confirmations_required)
+        .unwrap_or(0);
+    if blocks >= confirmations_required as u64 {
+        let event = state
+            .tracker
+            .wait_for_channel(user_channel_id, |event| {
+                matches!(event, NodeEvent::ChannelReady { .. })
+            })
+            .await;
+        check_not_closed(&event)?;
+    }
+    Ok(())
+}
+
+/// Fails if `pubkey` is the payer node we spawned and it doesn't accept our channels as
+/// zero-conf. Whether any other peer trusts us is only known once the channel is opened
+fn check_payer_trusts_us(state: &AppState, pubkey: PublicKey) -> Result<(), ApiError> {
+    let Some(payer_api_url) = &state.payer_api_url else {
+        return Ok(());
+    };
+    let Ok(payer) = LspsClient::new(payer_api_url).get_lsps_config() else {
+        return Ok(());
+    };
+    if payer.pubkey == pubkey && !payer.trusted_peers_0conf.contains(&state.node.node_id()) {
+        return Err(ApiError::bad_request(
+            "zero_conf_untrusted",
+            "the payer node doesn't accept zero-conf channels from us, see --payer-trusts-lsp-0conf",
+        )
+        .with_details(pubkey));
+    }
+    Ok(())
+}
+
+/// Waits for a zero-conf channel to be ready, fails if the peer wants it confirmed first
+async fn ready_zero_conf_channel(state: &AppState, user_channel_id: u128) -> Result<(), ApiError> {
+    let event = state
+        .tracker
+        .wait_for_channel(user_channel_id, |_| true)
+        .await;
+    check_not_closed(&event)?;
+
+    let channel = find_channel(state, user_channel_id);
+    let confirmations_required = channel
+        .as_ref()
+        .and_then(|channel| channel.confirmations_required)
+        .unwrap_or(0);
+    if confirmations_required > 0 {
+        // the funding transaction is already broadcast, tell the caller which channel it is. A
+        // json value can't hold a u128, so the id is a string
+        let funding_txo = channel
+            .and_then(|channel| channel.funding_txo)
+            .map(|txo| txo.to_string());
+        return Err(ApiError::new(
+            axum::http::StatusCode::CONFLICT,
+            "zero_conf_rejected",
+            "the peer requires the channel to be confirmed, it must trust us for zero-conf",
+        )
+        .with_details(json!({
+            "user_channel_id": user_channel_id.to_string(),
+            "funding_txo": funding_txo,
+            "confirmations_required": confirmations_required,
+        })));
+    }
+
+    let event = state
+        .tracker
+        .wait_for_channel(user_channel_id, |event| {
+            matches!(event, NodeEvent::ChannelReady { .. })
+        })
+        .await;
+    check_not_closed(&event)
+}
+
+/// Fails if the channel was closed while we were waiting for it
+fn check_not_closed(event: &NodeEvent) -> Result<(), ApiError> {
+    if let NodeEvent::ChannelClosed { reason, .. } = event {
+        let mut err = ApiError::new(
+            axum::http::StatusCode::CONFLICT,
+            "channel_closed",
+            "channel was closed before becoming ready",
+        );
+        if let Some(reason) = reason {
+            err = err.with_details(reason);
+        }
+        return Err(err);
+    }
+    Ok(())
+}
+
+async fn list_channels(
+    State(state): State<AppState>,
+    Query(query): Query<ListChannelsQuery>,
+) -> ApiResult<ListChannelsResponse> {
     let channels = state
         .node
         .list_channels()
         .into_iter()
-        .map(|channel| channel.into())
+        .map(CompactChannel::from)
+        .filter(|channel| query.matches(channel))
         .collect::<Vec<_>>();
 
-    Json(ListChannelsResponse { channels })
+    Ok(Json(ListChannelsResponse { channels }))
+}
+
+async fn close_channel(
+    State(state): State<AppState>,
+    Path(user_channel_id): Path<u128>,
+    Json(req): Json<CloseChannelRequest>,
+) -> ApiResult<CloseChannelResponse> {
+    close_and_sweep(&state, user_channel_id, req, false).await
+}
+
+async fn force_close_channel(
+    State(state): State<AppState>,
+    Path(user_channel_id): Path<u128>,
+    Json(req): Json<CloseChannelRequest>,
+) -> ApiResult<CloseChannelResponse> {
+    close_and_sweep(&state, user_channel_id, req, true).await
+}
+
+async fn close_and_sweep(
+    state: &AppState,
+    user_channel_id: u128,
+    req: CloseChannelRequest,
+    force: bool,
+) -> ApiResult<CloseChannelResponse> {
+    if req.mine_until_swept && state.bitcoin.is_none() {
+        return Err(mining_unavailable());
+    }
+
+    let id = UserChannelId(user_channel_id);
+    if force {
+        state
+            .node
+            .force_close_channel(&id, req.counterparty_node_id, req.force_close_reason)?;
+    } else {
+        state.node.close_channel(&id, req.counterparty_node_id)?;
+    }
+    // only once the close started, a failed call leaves the channel open
+    state
+        .closing_channels
+        .lock()
+        .unwrap()
+        .insert(user_channel_id);
+
+    let blocks_mined = if req.mine_until_swept {
+        mine_until_swept(state, user_channel_id).await?
+    } else {
+        0
+    };
+
+    Ok(Json(CloseChannelResponse {
+        user_channel_id,
+        blocks_mined,
+    }))
+}
+
+fn mining_unavailable() -> ApiError {
+    ApiError::bad_request(
+        "mining_unavailable",
+        "blocks can only be mined when the node runs its own bitcoind",
+    )
+}
+
+/// Mines `blocks` blocks, waits for esplora to index them and syncs the node
+fn mine_and_sync(state: &AppState, blocks: u64) -> Result<(), ApiError> {
+    mine_to_and_sync(state, blocks, None)?;
+    Ok(())
+}
+
+/// Mines `blocks` blocks to `address` or to a bitcoind wallet address, waits for esplora to
+/// index them and syncs the node, returns the hashes of the mined blocks
+fn mine_to_and_sync(
+    state: &AppState,
+    blocks: u64,
+    address: Option<&str>,
+) -> Result<Vec<String>, ApiError> {
+    let (Some(bitcoin), Some(esplora)) = (&state.bitcoin, &state.esplora) else {
+        return Err(mining_unavailable());
+    };
+    let miner_address = match address {
+        Some(address) => electrsd::bitcoind::bitcoincore_rpc::bitcoin::Address::from_str(address)
+            .map_err(|e| {
+                ApiError::bad_request("invalid_address", "could not parse address").with_details(e)
+            })?
+            .assume_checked(),
+        None => bitcoin.client.get_new_address(None, None)?.assume_checked(),
+    };
+    let block_hashes = bitcoin.client.generate_to_address(blocks, &miner_address)?;
+    let info = bitcoin.client.get_blockchain_info()?;
+    esplora.wait_height(info.blocks as usize);
+    state.node.sync_wallets()?;
+
+    Ok(block_hashes.iter().map(|hash| hash.to_string()).collect())
+}
+
+fn chain_tip(state: &AppState) -> ChainTip {
+    let best_block = state.node.status().current_best_block;
+    ChainTip {
+        height: best_block.height,
+        block_hash: best_block.block_hash.to_string(),
+    }
+}
+
+async fn mine_blocks(
+    State(state): State<AppState>,
+    Json(req): Json<MineBlocksRequest>,
+) -> ApiResult<MineBlocksResponse> {
+    let block_hashes = mine_to_and_sync(&state, req.count, req.address.as_deref())?;
+    Ok(Json(MineBlocksResponse {
+        block_hashes,
+        tip: chain_tip(&state),
+    }))
+}
+
+async fn get_chain_tip(State(state): State<AppState>) -> ApiResult<ChainTip> {
+    Ok(Json(chain_tip(&state)))
+}
+
+/// How long esplora and the node may take to follow a reorg
+const REORG_SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+async fn reorg(
+    State(state): State<AppState>,
+    Json(req): Json<ReorgRequest>,
+) -> ApiResult<ReorgResponse> {
+    let (Some(bitcoin), Some(esplora)) = (&state.bitcoin, &state.esplora) else {
+        return Err(mining_unavailable());
+    };
+    let height = bitcoin.client.get_block_count()?;
+    if req.depth == 0 || req.depth > height {
+        return Err(ApiError::bad_request(
+            "invalid_depth",
+            format!("depth must be between 1 and {}", height),
+        ));
+    }
+
+    let blocks = req.mine_blocks.unwrap_or(req.depth + 1);
+    if let Some(txids) = &req.include_txids {
+        if !txids.is_empty() && blocks == 0 {
+            return Err(ApiError::bad_request(
+                "invalid_mine_blocks",
+                "mine_blocks must be at least 1 to include transactions",
+            ));
+        }
+        check_reorg_txids(bitcoin, height, req.depth, txids)?;
+    }
+
+    let invalidated_hash = bitcoin.client.get_block_hash(height - req.depth + 1)?;
+    bitcoin.client.invalidate_block(&invalidated_hash)?;
+
+    let miner_address = bitcoin.client.get_new_address(None, None)?.assume_checked();
+    let block_hashes = match req.include_txids {
+        None => bitcoin
+            .client
+            .generate_to_address(blocks, &miner_address)?
+            .iter()
+            .map(|hash| hash.to_string())
+            .collect(),
+        Some(txids) => {
+            // generateblock only includes the given transactions, unlike generatetoaddress
+            let mut block_hashes = Vec::new();
+            let mut txids = Some(txids);
+            for _ in 0..blocks {
+                let args = [
+                    json!(miner_address.to_string()),
+                    json!(txids.take().unwrap_or_default()),
+                ];
+                let block = bitcoin.client.call::<Value>("generateblock", &args)?;
+                block_hashes.push(block["hash"].as_str().unwrap_or_default().to_string());
+            }
+            block_hashes
+        }
+    };
+
+    let best_hash = bitcoin.client.get_best_block_hash()?.to_string();
+    wait_for_tip(&state, esplora, &best_hash).await?;
+
+    Ok(Json(ReorgResponse {
+        invalidated_block_hash: invalidated_hash.to_string(),
+        block_hashes,
+        tip: chain_tip(&state),
+    }))
+}
+
+/// Fails unless every txid is in the mempool or in the blocks the reorg disconnects, checked
+/// before invalidating anything so a bad request leaves the chain untouched
+fn check_reorg_txids(
+    bitcoin: &electrsd::bitcoind::BitcoinD,
+    height: u64,
+    depth: u64,
+    txids: &[String],
+) -> Result<(), ApiError> {
+    // transactions of the disconnected blocks go back to the mempool, coinbases don't
+    let mut disconnected = HashSet::new();
+    for height in height - depth + 1..=height {
+        let block_hash = bitcoin.client.get_block_hash(height)?;
+        let block = bitcoin.client.get_block(&block_hash)?;
+        disconnected.extend(block.txdata.iter().skip(1).map(|tx| tx.txid()));
+    }
+
+    for txid in txids {
+        let parsed =
+            electrsd::bitcoind::bitcoincore_rpc::bitcoin::Txid::from_str(txid).map_err(|e| {
+                ApiError::bad_request("invalid_txid", "could not parse txid").with_details(e)
+            })?;
+        if !disconnected.contains(&parsed) && bitcoin.client.get_mempool_entry(&parsed).is_err() {
+            return Err(ApiError::bad_request(
+                "unknown_txid",
+                "transaction is neither in the mempool nor in the disconnected blocks",
+            )
+            .with_details(txid));
+        }
+    }
+    Ok(())
+}
+
+/// Waits until esplora and then the node have both switched to the chain ending in `block_hash`
+async fn wait_for_tip(
+    state: &AppState,
+    esplora: &electrsd::ElectrsD,
+    block_hash: &str,
+) -> Result<(), ApiError> {
+    let url = format!(
+        "http://{}/blocks/tip/hash",
+        esplora.esplora_url.clone().unwrap_or_default()
+    );
+    let deadline = tokio::time::Instant::now() + REORG_SYNC_TIMEOUT;
+    loop {
+        let esplora_tip = minreq::get(&url)
+            .send()
+            .ok()
+            .and_then(|res| res.as_str().ok().map(|tip| tip.trim().to_string()));
+        if esplora_tip.as_deref() == Some(block_hash) {
+            state.node.sync_wallets()?;
+            if chain_tip(state).block_hash == block_hash {
+                return Ok(());
+            }
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            return Err(ApiError::new(
+                axum::http::StatusCode::GATEWAY_TIMEOUT,
+                "sync_timeout",
+                "esplora or the node did not follow the new tip in time",
+            )
+            .with_details(block_hash));
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+/// Blocks mined at once while waiting for a closed channel to be swept
+const SWEEP_BLOCKS_PER_ROUND: u64 = 6;
+
+/// Gives up sweeping after this many blocks, well above the default `to_self_delay` of 144
+const MAX_SWEEP_BLOCKS: u64 = 2016;
+
+/// How long the channel may take to close before we give up on sweeping it
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Waits for the channel to close, then mines until nothing is left to claim from it
+async fn mine_until_swept(state: &AppState, user_channel_id: u128) -> Result<u64, ApiError> {
+    let closed = tokio::time::timeout(
+        CLOSE_TIMEOUT,
+        state.tracker.wait_for_channel(user_channel_id, |_| false),
+    )
+    .await
+    .map_err(|_| {
+        ApiError::new(
+            axum::http::StatusCode::GATEWAY_TIMEOUT,
+            "close_timeout",
+            "channel did not close in time",
+        )
+    })?;
+    let NodeEvent::ChannelClosed { channel_id, .. } = closed else {
+        unreachable!("closed channels always match");
+    };
+
+    let mut blocks_mined = 0;
+    loop {
+        // leave the node some time to broadcast its claims before mining them
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let (status, _) = sweep_status(&state.node.list_balances(), &channel_id);
+        if status == SweepStatus::Swept {
+            return Ok(blocks_mined);
+        }
+        if blocks_mined >= MAX_SWEEP_BLOCKS {
+            return Err(ApiError::new(
+                axum::http::StatusCode::GATEWAY_TIMEOUT,
+                "sweep_timeout",
+                format!("funds were not swept after {} blocks", blocks_mined),
+            )
+            .with_details(format!("{:?}", status)));
+        }
+        mine_and_sync(state, SWEEP_BLOCKS_PER_ROUND)?;
+        blocks_mined += SWEEP_BLOCKS_PER_ROUND;
+    }
+}
+
+/// Least advanced sweep status of the channel's funds and the amount that isn't swept yet
+fn sweep_status(balances: &BalanceDetails, channel_id: &str) -> (SweepStatus, u64) {
+    let mut status = SweepStatus::Swept;
+    let mut pending_sats = 0;
+
+    for balance in balances.lightning_balances.iter().map(ChannelBalance::from) {
+        if balance.channel_id() == channel_id {
+            status = SweepStatus::AwaitingClaim;
+            pending_sats += balance.amount_sats();
+        }
+    }
+
+    for balance in balances
+        .pending_balances_from_channel_closures
+        .iter()
+        .map(PendingSweep::from)
+    {
+        if balance.channel_id() == Some(channel_id) {
+            let balance_status = match balance {
+                PendingSweep::PendingBroadcast { .. } => SweepStatus::PendingBroadcast,
+                PendingSweep::BroadcastAwaitingConfirmation { .. }
+                | PendingSweep::AwaitingThresholdConfirmations { .. } => {
+                    SweepStatus::AwaitingConfirmation
+                }
+            };
+            status = status.min(balance_status);
+            pending_sats += balance.amount_sats();
+        }
+    }
+
+    (status, pending_sats)
+}
+
+async fn list_closed_channels(
+    State(state): State<AppState>,
+) -> ApiResult<ListClosedChannelsResponse> {
+    let balances = state.node.list_balances();
+    let mut channels = state
+        .tracker
+        .closed_channels()
+        .into_iter()
+        .filter_map(|event| match event {
+            NodeEvent::ChannelClosed {
+                channel_id,
+                user_channel_id,
+                counterparty_node_id,
+                reason,
+            } => {
+                let (sweep_status, pending_sweep_sats) = sweep_status(&balances, &channel_id);
+                Some(ClosedChannel {
+                    channel_id,
+                    user_channel_id,
+                    counterparty_node_id,
+                    reason,
+                    sweep_status,
+                    pending_sweep_sats,
+                })
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    channels.sort_by_key(|channel| channel.user_channel_id);
+
+    Ok(Json(ListClosedChannelsResponse { channels }))
 }
 
 async fn pay_invoice(
     State(state): State<AppState>,
     Json(req): Json<PayInvoiceRequest>,
-) -> Json<PayInvoiceResponse> {
-    let invoice = Bolt11Invoice::from_str(&req.invoice).unwrap();
-    let res = state.node.bolt11_payment().send(&invoice, None).unwrap();
-    Json(PayInvoiceResponse {
+) -> ApiResult<PayInvoiceResponse> {
+    let invoice = Bolt11Invoice::from_str(&req.invoice).map_err(|e| {
+        ApiError::bad_request("invalid_invoice", "could not parse bolt11 invoice").with_details(e)
+    })?;
+    let res = state.node.bolt11_payment().send(&invoice, None)?;
+    Ok(Json(PayInvoiceResponse {
         payment_id: res.to_string(),
-    })
+    }))
 }
 
 async fn get_invoice(
     State(state): State<AppState>,
     Json(req): Json<GetInvoiceRequest>,
-) -> Json<GetInvoiceResponse> {
-    let description = ldk_node::lightning_invoice::Bolt11InvoiceDescription::Direct(
-        ldk_node::lightning_invoice::Description::new(req.description.clone()).unwrap(),
-    );
+) -> ApiResult<GetInvoiceResponse> {
+    let description = ldk_node::lightning_invoice::Description::new(req.description.clone())
+        .map_err(|e| {
+            ApiError::bad_request("invalid_description", "invalid invoice description")
+                .with_details(e)
+        })?;
+    let description = ldk_node::lightning_invoice::Bolt11InvoiceDescription::Direct(description);
+    let invoice = state.node.bolt11_payment().receive(
+        sats_to_msat("amount_sats", req.amount_sats)?,
+        &description,
+        req.expiry_secs,
+    )?;
+
+    Ok(Json(GetInvoiceResponse {
+        invoice: invoice.to_string(),
+    }))
+}
+
+async fn jit_invoice(
+    State(state): State<AppState>,
+    Json(req): Json<JitInvoiceRequest>,
+) -> ApiResult<GetInvoiceResponse> {
+    let description = ldk_node::lightning_invoice::Description::new(req.description.clone())
+        .map_err(|e| {
+            ApiError::bad_request("invalid_description", "invalid invoice description")
+                .with_details(e)
+        })?;
+    let description = ldk_node::lightning_invoice::Bolt11InvoiceDescription::Direct(description);
+    let bolt11_payment = state.node.bolt11_payment();
+    let invoice = match req.amount_msat {
+        Some(amount_msat) => bolt11_payment.receive_via_jit_channel(
+            amount_msat,
+            &description,
+            req.expiry_secs,
+            req.max_total_lsp_fee_limit_msat,
+        )?,
+        None => bolt11_payment.receive_variable_amount_via_jit_channel(
+            &description,
+            req.expiry_secs,
+            req.max_proportional_lsp_fee_limit_ppm_msat,
+        )?,
+    };
+
+    Ok(Json(GetInvoiceResponse {
+        invoice: invoice.to_string(),
+    }))
+}
+
+async fn get_offer(
+    State(state): State<AppState>,
+    Json(req): Json<GetOfferRequest>,
+) -> ApiResult<GetOfferResponse> {
+    let bolt12_payment = state.node.bolt12_payment();
+    let offer = match req.amount_msat {
+        Some(amount_msat) => {
+            bolt12_payment.receive(amount_msat, &req.description, req.expiry_secs, req.quantity)?
+        }
+        None => bolt12_payment.receive_variable_amount(&req.description, req.expiry_secs)?,
+    };
+
+    Ok(Json(GetOfferResponse {
+        offer: offer.to_string(),
+    }))
+}
+
+async fn pay_offer(
+    State(state): State<AppState>,
+    Json(req): Json<PayOfferRequest>,
+) -> ApiResult<PayOfferResponse> {
+    let offer = Offer::from_str(&req.offer).map_err(|e| {
+        ApiError::bad_request("invalid_offer", "could not parse bolt12 offer")
+            .with_details(format!("{:?}", e))
+    })?;
+    let bolt12_payment = state.node.bolt12_payment();
+    let payment_id = match req.amount_msat {
+        Some(amount_msat) => {
+            bolt12_payment.send_using_amount(&offer, amount_msat, req.quantity, req.payer_note)?
+        }
+        None => bolt12_payment.send(&offer, req.quantity, req.payer_note)?,
+    };
+
+    Ok(Json(PayOfferResponse {
+        payment_id: payment_id.0.to_lower_hex_string(),
+    }))
+}
+
+async fn initiate_refund(
+    State(state): State<AppState>,
+    Json(req): Json<InitiateRefundRequest>,
+) -> ApiResult<InitiateRefundResponse> {
+    let refund = state.node.bolt12_payment().initiate_refund(
+        req.amount_msat,
+        req.expiry_secs,
+        req.quantity,
+        req.payer_note,
+    )?;
+
+    Ok(Json(InitiateRefundResponse {
+        refund: refund.to_string(),
+    }))
+}
+
+async fn request_refund(
+    State(state): State<AppState>,
+    Json(req): Json<RequestRefundRequest>,
+) -> ApiResult<RequestRefundResponse> {
+    let refund = Refund::from_str(&req.refund).map_err(|e| {
+        ApiError::bad_request("invalid_refund", "could not parse bolt12 refund")
+            .with_details(format!("{:?}", e))
+    })?;
     let invoice = state
         .node
-        .bolt11_payment()
-        .receive(req.amount_sats * 1000, &description, req.expiry_secs)
-        .unwrap();
+        .bolt12_payment()
+        .request_refund_payment(&refund)?;
 
-    Json(GetInvoiceResponse {
-        invoice: invoice.to_string(),
-    })
+    Ok(Json(RequestRefundResponse {
+        payment_hash: invoice.payment_hash().0.to_lower_hex_string(),
+        amount_msat: invoice.amount_msats(),
+    }))
 }
 
-async fn sync(State(state): State<AppState>) -> Json<Value> {
-    state.node.sync_wallets().unwrap();
-    Json(json!({"synced": true}))
+async fn keysend(
+    State(state): State<AppState>,
+    Json(req): Json<KeysendRequest>,
+) -> ApiResult<KeysendResponse> {
+    let custom_tlvs = req
+        .custom_tlvs
+        .iter()
+        .map(|tlv| {
+            let value = Vec::<u8>::from_hex(&tlv.value).map_err(|e| {
+                ApiError::bad_request("invalid_custom_tlv", "custom tlv value must be hex")
+                    .with_details(format!("type {}: {}", tlv.type_num, e))
+            })?;
+            Ok(CustomTlvRecord {
+                type_num: tlv.type_num,
+                value,
+            })
+        })
+        .collect::<Result<Vec<_>, ApiError>>()?;
+
+    let spontaneous_payment = state.node.spontaneous_payment();
+    let payment_id = if custom_tlvs.is_empty() {
+        spontaneous_payment.send(req.amount_msat, req.pubkey, None)?
+    } else {
+        spontaneous_payment.send_with_custom_tlvs(req.amount_msat, req.pubkey, None, custom_tlvs)?
+    };
+
+    Ok(Json(KeysendResponse {
+        payment_id: payment_id.0.to_lower_hex_string(),
+    }))
 }
 
-async fn get_balance(State(state): State<AppState>) -> Json<GetBalanceResponse> {
-    let balances = state.node.list_balances();
-    Json(GetBalanceResponse {
-        total_onchain_balance_sats: balances.total_onchain_balance_sats,
-        spendable_onchain_balance_sats: balances.spendable_onchain_balance_sats,
-    })
+async fn sync(State(state): State<AppState>) -> ApiResult<Value> {
+    state.node.sync_wallets()?;
+    Ok(Json(json!({"synced": true})))
+}
+
+async fn get_balance(State(state): State<AppState>) -> ApiResult<GetBalanceResponse> {
+    Ok(Json(GetBalanceResponse::from(&state.node.list_balances())))
 }
 
 async fn get_payment(
     State(state): State<AppState>,
     Path(payment_id): Path<String>,
-) -> Json<GetPaymentResponse> {
-    let payment_id_bytes = <[u8; 32]>::from_hex(&payment_id).unwrap();
+) -> ApiResult<GetPaymentResponse> {
+    let payment_id_bytes = <[u8; 32]>::from_hex(&payment_id).map_err(|e| {
+        ApiError::bad_request("invalid_payment_id", "payment id must be 32 bytes of hex")
+            .with_details(e)
+    })?;
     let payment_id = PaymentId(payment_id_bytes);
-    let payment = state.node.payment(&payment_id).unwrap();
+    let payment = state.node.payment(&payment_id).ok_or_else(|| {
+        ApiError::not_found(
+            "payment_not_found",
+            format!("no payment with id {}", payment_id),
+        )
+    })?;
 
-    Json(GetPaymentResponse {
-        status: match payment.status {
-            ldk_node::payment::PaymentStatus::Pending => "pending".to_string(),
-            ldk_node::payment::PaymentStatus::Succeeded => "succeeded".to_string(),
-            ldk_node::payment::PaymentStatus::Failed => "failed".to_string(),
-        },
-    })
+    // failure reasons and custom records are only carried by the events, not the payment store
+    let event = state
+        .tracker
+        .payment_event(&payment_id.0.to_lower_hex_string());
+    let (failure_reason, custom_tlvs) = match event {
+        Some(NodeEvent::PaymentReceived { custom_tlvs, .. }) => (None, custom_tlvs),
+        Some(NodeEvent::PaymentFailed { reason, .. }) => (reason, Vec::new()),
+        _ => (None, Vec::new()),
+    };
+
+    Ok(Json(GetPaymentResponse {
+        payment: Payment::from(&payment),
+        failure_reason,
+        custom_tlvs,
+    }))
+}
+
+async fn list_payments(
+    State(state): State<AppState>,
+    Query(query): Query<ListPaymentsQuery>,
+) -> ApiResult<ListPaymentsResponse> {
+    let mut payments = state
+        .node
+        .list_payments()
+        .iter()
+        .map(Payment::from)
+        .filter(|payment| query.matches(payment))
+        .collect::<Vec<_>>();
+    payments.sort_by(|a, b| {
+        (a.latest_update_timestamp, &a.payment_id).cmp(&(b.latest_update_timestamp, &b.payment_id))
+    });
+
+    let total = payments.len();
+    let payments = payments
+        .into_iter()
+        .skip(query.offset.unwrap_or(0))
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    Ok(Json(ListPaymentsResponse { payments, total }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODE_A: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const NODE_B: &str = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+
+    fn pubkey(node_id: &str) -> PublicKey {
+        PublicKey::from_str(node_id).unwrap()
+    }
+
+    fn error_code<T: std::fmt::Debug>(res: Result<T, ApiError>) -> String {
+        res.unwrap_err().body.code
+    }
+
+    fn parse_args(args: &[&str]) -> LspArgs {
+        LspArgs::from_args(&["lspsd"], args).unwrap()
+    }
+
+    /// Writes `contents` to a temporary config file, deleted when the returned handle is dropped
+    fn config_file_with(contents: serde_json::Value) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), contents.to_string()).unwrap();
+        file
+    }
+
+    #[test]
+    fn parse_peer_address_accepts_pubkey_and_uri_forms() {
+        let localhost = SocketAddress::TcpIpV4 {
+            addr: [127, 0, 0, 1],
+            port: 9735,
+        };
+        let uri = format!("{}@127.0.0.1:9735", NODE_A);
+
+        let parsed = parse_peer_address(Some(pubkey(NODE_A)), "127.0.0.1:9735").unwrap();
+        assert_eq!(parsed, (pubkey(NODE_A), localhost.clone()));
+        let parsed = parse_peer_address(None, &uri).unwrap();
+        assert_eq!(parsed, (pubkey(NODE_A), localhost.clone()));
+        let parsed = parse_peer_address(Some(pubkey(NODE_A)), &uri).unwrap();
+        assert_eq!(parsed, (pubkey(NODE_A), localhost));
+
+        let (_, address) = parse_peer_address(Some(pubkey(NODE_A)), "localhost:9735").unwrap();
+        assert!(matches!(
+            address,
+            SocketAddress::Hostname { port: 9735, .. }
+        ));
+    }
+
+    #[test]
+    fn parse_peer_address_rejects_bad_forms() {
+        let uri = format!("{}@127.0.0.1:9735", NODE_A);
+        assert_eq!(
+            error_code(parse_peer_address(Some(pubkey(NODE_B)), &uri)),
+            "pubkey_mismatch"
+        );
+        assert_eq!(
+            error_code(parse_peer_address(None, "127.0.0.1:9735")),
+            "missing_pubkey"
+        );
+        assert_eq!(
+            error_code(parse_peer_address(None, "nope@127.0.0.1:9735")),
+            "invalid_public_key"
+        );
+        assert_eq!(
+            error_code(parse_peer_address(Some(pubkey(NODE_A)), "127.0.0.1")),
+            "invalid_socket_address"
+        );
+        let uri_without_port = format!("{}@127.0.0.1", NODE_A);
+        assert_eq!(
+            error_code(parse_peer_address(None, &uri_without_port)),
+            "invalid_socket_address"
+        );
+    }
+
+    #[test]
+    fn config_file_defaults_without_file_or_options() {
+        assert_eq!(parse_args(&[]).config_file(), ConfigFile::default());
+    }
+
+    #[test]
+    fn config_file_command_line_overrides_file() {
+        let file = config_file_with(json!({
+            "lsps2": {
+                "require_token": "file-token",
+                "channel_opening_fee_ppm": 1000,
+                "min_payment_size_msat": 2000,
+            },
+            "lsps1": { "fee_ppm": 10, "order_expiry_secs": 60 },
+        }));
+        let path = file.path().to_str().unwrap();
+
+        let config = parse_args(&[
+            "--config-file",
+            path,
+            "--lsps2-require-token",
+            "cli-token",
+            "--lsps2-channel-opening-fee-ppm",
+            "5000",
+            "--lsps1-fee-ppm",
+            "20",
+        ])
+        .config_file();
+
+        assert_eq!(config.lsps2.require_token.as_deref(), Some("cli-token"));
+        assert_eq!(config.lsps2.channel_opening_fee_ppm, 5000);
+        // options missing on the command line keep the file's value, or the default
+        assert_eq!(config.lsps2.min_payment_size_msat, 2000);
+        assert_eq!(
+            config.lsps2.max_client_to_self_delay,
+            Lsps2Config::default().max_client_to_self_delay
+        );
+        let lsps1 = config.lsps1.unwrap();
+        assert_eq!(lsps1.fee_ppm, 20);
+        assert_eq!(lsps1.order_expiry_secs, 60);
+    }
+
+    #[test]
+    fn config_file_token_required_keeps_a_given_token() {
+        let config = parse_args(&["--lsps2-token-required"]).config_file();
+        assert_eq!(
+            config.lsps2.require_token.map(|token| token.len()),
+            Some(32)
+        );
+
+        let file = config_file_with(json!({ "lsps2": { "require_token": "file-token" } }));
+        let path = file.path().to_str().unwrap();
+        let config = parse_args(&["--config-file", path, "--lsps2-token-required"]).config_file();
+        assert_eq!(config.lsps2.require_token.as_deref(), Some("file-token"));
+    }
+
+    #[test]
+    fn config_file_lsps1_switch_enables_the_service() {
+        let config = parse_args(&["--lsps1", "--lsps1-fee-base-sat", "100"]).config_file();
+        let lsps1 = config.lsps1.unwrap();
+        assert_eq!(lsps1.fee_base_sat, 100);
+        assert_eq!(
+            lsps1.order_expiry_secs,
+            Lsps1Config::default().order_expiry_secs
+        );
+
+        let config = parse_args(&["--lsps1-fee-base-sat", "100"]).config_file();
+        assert_eq!(config.lsps1, None);
+    }
 }